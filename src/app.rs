@@ -1,10 +1,39 @@
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
+use ratatui::layout::{Position, Rect};
+use regex::Regex;
 
 use serde::{Deserialize, Serialize};
 
-use crate::file_tree::{build_tree, TreeNode};
-use crate::markdown::{render_markdown, RenderedLine, TextSegment};
+use crate::check::{is_external, percent_decode};
+use crate::command::{self, ExCommand, SetOption};
+use crate::file_tree::{
+    file_meta, list_dir, load_root, matches_terms, sort_children, NodeMeta, SortMode, TreeNode, TreeOptions,
+};
+use crate::fileops::{self, FileOp};
+use crate::filetype::{render_document, DocKind, FileTypes};
+use crate::git::{self, GitStatuses};
+use crate::keys::{KeySequence, Motion};
+use crate::markdown::{heading_slug, RenderedLine, TextSegment};
+use crate::scanner::{self, ScanEvent, Scanner};
+use crate::index::SharedIndex;
+use crate::search::{self, ResultOrder, SearchEvent, SearchHistory, SearchJob, SearchOptions, SearchResult, Searcher};
+use crate::title::TitleCache;
+use crate::watcher::{FsChanges, FsWatcher};
+
+/// Narrowest the sidebar and content panes get, in columns
+const MIN_SIDEBAR_WIDTH: u16 = 12;
+const MIN_CONTENT_WIDTH: u16 = 20;
+const LAST_SETTING: usize = 6; // Settings overlay items are 0..=6
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Focus {
@@ -18,8 +47,11 @@ pub enum AppMode {
     Search,
     Settings,
     DocumentSearch,
-    Select, // Mode for text selection (disables mouse capture)
-    About,  // About window
+    Select,  // Mode for text selection (disables mouse capture)
+    About,   // About window
+    Command, // Ex-style `:` command line
+    Filter,  // Typing a sidebar filter
+    FileOp,  // Prompt for a sidebar file operation
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
@@ -61,11 +93,50 @@ impl WrapWidth {
     }
 }
 
+/// What the sidebar shows for each document
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum SidebarLabel {
+    #[default]
+    FileName,
+    Title, // Front-matter title or first H1, falling back to the file name
+}
+
+impl SidebarLabel {
+    pub fn display_name(self) -> &'static str {
+        match self {
+            SidebarLabel::FileName => "File names",
+            SidebarLabel::Title => "Titles",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            SidebarLabel::FileName => SidebarLabel::Title,
+            SidebarLabel::Title => SidebarLabel::FileName,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Settings {
     pub show_line_numbers: bool,
     pub theme: Theme,
     pub wrap_width: WrapWidth,
+    pub file_types: FileTypes,
+    pub sort_mode: SortMode,
+    pub show_modified_column: bool,
+    pub show_word_count_column: bool,
+    pub sidebar_label: SidebarLabel,
+    /// Sidebar width as a percentage of the terminal width
+    pub sidebar_width: u16,
+    pub show_sidebar: bool,
+    /// Terminal width (in columns) below which the sidebar only shows while it has focus
+    pub sidebar_collapse_below: u16,
+    /// Per-editor arguments for opening `{file}` at `{line}`, e.g. `"code": "--goto {file}:{line}"`
+    pub editor_line_args: HashMap<String, String>,
+    /// Regex, case and whole-word toggles the search prompts start with
+    pub search: SearchOptions,
 }
 
 impl Default for Settings {
@@ -74,6 +145,16 @@ impl Default for Settings {
             show_line_numbers: true,
             theme: Theme::Dark,
             wrap_width: WrapWidth::Chars120,
+            file_types: FileTypes::default(),
+            sort_mode: SortMode::Name,
+            show_modified_column: false,
+            show_word_count_column: false,
+            sidebar_label: SidebarLabel::FileName,
+            sidebar_width: 30,
+            show_sidebar: true,
+            sidebar_collapse_below: 60,
+            editor_line_args: HashMap::new(),
+            search: SearchOptions::default(),
         }
     }
 }
@@ -102,13 +183,39 @@ impl Settings {
             }
         }
     }
+
+    /// Tree options derived from these settings
+    pub fn tree_options(&self, show_ignored: bool) -> TreeOptions {
+        TreeOptions {
+            show_ignored,
+            file_types: self.file_types.clone(),
+            sort_mode: self.sort_mode,
+            load_contents: self.show_word_count_column,
+        }
+    }
+}
+
+/// One occurrence of the document search query
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocMatch {
+    pub line: usize,         // Index into the rendered lines
+    pub range: Range<usize>, // Bytes of the line's text as drawn (`to_plain_string`)
+}
+
+/// A row of the global search results: a document, or one of its hits
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchRow {
+    File(usize),
+    Hit(usize, usize),
 }
 
-#[derive(Debug, Clone)]
-pub struct SearchResult {
-    pub path: PathBuf,
-    pub name: String,
-    pub match_preview: String,
+/// Where the panes were drawn, so mouse events can be mapped back to them
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PaneAreas {
+    pub sidebar: Option<Rect>,      // Including its borders
+    pub content: Rect,              // Document text, inside the borders
+    pub scrollbar: Option<Rect>,    // Only when the document is longer than the pane
+    pub overlay_list: Option<Rect>, // Search results or settings, inside the borders
 }
 
 pub struct App {
@@ -119,24 +226,108 @@ pub struct App {
     pub current_file: Option<PathBuf>,
     pub rendered_content: Vec<RenderedLine>,
     pub content_height: u16,
+    pub layout_width: u16,      // Width shared by the sidebar and content, as of the last draw
+    pub resizing_sidebar: bool, // The sidebar border is being dragged
+    pub areas: PaneAreas,       // As of the last draw
+    pub sidebar_scroll: usize,  // First visible sidebar row
+    sidebar_fitted: Option<(usize, usize)>, // Selection and height the sidebar was last scrolled to
+    dragging_scrollbar: bool,
+    last_click: Option<(Instant, u16, u16)>, // For double-clicks
     pub mode: AppMode,
     pub search_query: String,
     pub search_results: Vec<SearchResult>,
-    pub search_selected: usize,
-    #[allow(dead_code)]
+    search_row_starts: Vec<usize>, // Row each result is listed from
+    pub search_rows: usize,        // Rows the results take up, one per document and hit
+    pub search_selected: usize,    // Row of the selected result, see `search_row`
+    pub search_scroll: usize,      // First visible search result row
+    pub search_scanned: usize,  // Documents searched so far by the running search
+    pub search_total: usize,    // Documents the running search has to go through
+    pub search_indexing: Option<(usize, usize)>, // Documents indexed so far, out of how many
+    pub search_waiting: bool, // The running search has read as far down the ranking as is shown
+    searcher: Option<Searcher>, // Running global search
+    search_index: SharedIndex,
+    search_title_matches: usize, // Results whose title matches, listed first
     pub root_path: PathBuf,
     pub settings: Settings,
     pub settings_selected: usize,
     pub doc_search_query: String,
-    pub doc_search_matches: Vec<usize>, // Line indices that match
-    pub doc_search_current: usize,      // Current match index
-    pub pending_g: bool,                // Track if 'g' was pressed for 'gg' combo
+    pub doc_search_matches: Vec<DocMatch>, // Every occurrence, in document order
+    pub doc_search_current: usize,         // Current match index
+    pub doc_search_pattern: Option<Regex>, // Compiled query, for highlighting
+    pub search_error: Option<String>,      // Why the query doesn't compile
+    search_history: SearchHistory,
+    history_pos: Option<usize>, // The history entry shown in the open prompt
+    history_draft: String,      // What was typed before going through the history
+    pub keys: KeySequence,              // Pending count / multi-key sequence
+    pub command_line: String,
+    pub command_completions: Vec<String>,
+    pub command_completion_index: Option<usize>,
+    pub status_message: Option<String>,
+    pub should_quit: bool,
+    pub show_ignored: bool, // Show hidden and ignored entries in the sidebar
+    pub sidebar_filter: String,
+    pub file_op: Option<FileOp>,
+    pub file_op_input: String,
+    pub git_status: GitStatuses, // Empty outside a git work tree
+    git_status_rx: Option<Receiver<Option<GitStatuses>>>,
+    filter_saved_expansion: Option<HashSet<PathBuf>>, // Expansion state before filtering
+    watcher: Option<FsWatcher>,
+    scanner: Option<Scanner>,                        // Running background scan
+    scanned_dirs: HashMap<PathBuf, Option<NodeMeta>>, // Scan results, None for dirs without documents
+    scan_errors: HashMap<PathBuf, String>,           // Entries the scan could not read
+    documents: Vec<PathBuf>,                         // Every document, as of the last finished scan, in path order
+    scan_documents: Vec<PathBuf>,                    // Documents found so far by the running scan
+    document_meta: HashMap<PathBuf, NodeMeta>,       // Metadata of the documents scanned, for directory totals
+    titles: TitleCache,
+    pub from_stdin: bool,               // Showing Markdown piped in on stdin, without a sidebar
+    stdin: Option<Receiver<String>>,    // Text still arriving on stdin
+    stdin_text: String,                 // Everything read from stdin so far
+}
+
+/// Reading position expressed relative to the nearest heading above it,
+/// so it survives edits elsewhere in the document
+struct ScrollAnchor {
+    heading: Option<(String, usize)>, // Heading text and its occurrence number
+    offset: usize,                    // Wrapped lines between the heading and the top line
+    fallback: usize,                  // Raw scroll position if the heading is gone
 }
 
 impl App {
     pub fn new(root_path: PathBuf) -> Self {
-        let tree = build_tree(&root_path);
+        let settings = Settings::load();
+        let tree = load_root(&root_path, &settings.tree_options(false));
+        let mut app = Self::with_tree(root_path, settings, tree);
+        app.scanner = Some(Scanner::start(&app.root_path, app.settings.tree_options(false)));
+        app.watcher = Some(FsWatcher::new(&app.root_path));
+        app.git_status_rx = Some(git::load_in_background(&app.root_path));
+
+        let root = app.root_path.clone();
+        app.fill_titles(&root);
+        if !app.settings.show_sidebar {
+            app.focus = Focus::Content;
+        }
+
+        // Auto-select first markdown file if available
+        app.select_first_file();
+        app
+    }
+
+    /// View Markdown piped in on stdin, re-rendered as more of it arrives.
+    /// There is no sidebar; `root_path` is only used to resolve `:open`.
+    pub fn from_stdin(root_path: PathBuf, stdin: Receiver<String>) -> Self {
+        let settings = Settings::load();
+        let mut tree = TreeNode::new("stdin".to_string(), root_path.clone(), true, 0);
+        tree.loaded = true;
+        let mut app = Self::with_tree(root_path, settings, tree);
+        app.from_stdin = true;
+        app.stdin = Some(stdin);
+        app.focus = Focus::Content;
+        app.rendered_content = Vec::new();
+        app
+    }
 
+    /// App state around an already loaded tree, with nothing running in the background
+    fn with_tree(root_path: PathBuf, settings: Settings, tree: TreeNode) -> Self {
         // Create welcome message as RenderedLines
         let welcome_content = vec![
             create_heading_line("Welcome to rmd!", 1),
@@ -150,8 +341,20 @@ impl App {
             create_plain_line("  l/→/Enter Open file / Expand directory"),
             create_plain_line("  h/←       Collapse directory / Go to parent"),
             create_plain_line("  Tab       Switch focus between sidebar and content"),
+            create_plain_line("  gt / gT   Next / previous tab"),
+            create_plain_line("  b         Show / hide the sidebar"),
+            create_plain_line("  < / >     Narrow / widen the sidebar"),
+            create_plain_line("  .         Show hidden and ignored files"),
+            create_plain_line("  f         Filter the sidebar by path"),
+            create_plain_line("  o         Cycle sidebar sort order"),
+            create_plain_line("  t         Show titles / file names in the sidebar"),
+            create_plain_line("  a / A     New file / directory"),
+            create_plain_line("  r / m / d Rename / move / delete"),
+            create_plain_line("  e         Edit in $EDITOR at the visible line"),
             create_plain_line("  gg        Go to top"),
             create_plain_line("  G         Go to bottom"),
+            create_plain_line("  ]] / [[   Next / previous heading"),
+            create_plain_line("  5j, 20G   Prefix motions with a count"),
             RenderedLine::new_empty(),
             create_heading_line("Scrolling", 2),
             RenderedLine::new_empty(),
@@ -163,16 +366,18 @@ impl App {
             create_heading_line("Search", 2),
             RenderedLine::new_empty(),
             create_plain_line("  /         Search in document"),
+            create_plain_line("  n / N     Next / previous match"),
             create_plain_line("  Ctrl+s    Search all files"),
             RenderedLine::new_empty(),
             create_heading_line("General", 2),
             RenderedLine::new_empty(),
+            create_plain_line("  :         Command line (:42, :open, :set, :theme, :q)"),
             create_plain_line("  v         Select mode (for copying text)"),
             create_plain_line("  q         Quit"),
             create_plain_line("  Ctrl+p    Settings"),
         ];
 
-        let mut app = Self {
+        Self {
             tree,
             selected_index: 0,
             content_scroll: 0,
@@ -180,22 +385,63 @@ impl App {
             current_file: None,
             rendered_content: welcome_content,
             content_height: 20,
+            layout_width: 80,
+            resizing_sidebar: false,
+            areas: PaneAreas::default(),
+            sidebar_scroll: 0,
+            sidebar_fitted: None,
+            dragging_scrollbar: false,
+            last_click: None,
             mode: AppMode::Normal,
             search_query: String::new(),
             search_results: Vec::new(),
+            search_row_starts: Vec::new(),
+            search_rows: 0,
             search_selected: 0,
-            root_path: root_path.clone(),
-            settings: Settings::load(),
+            search_scroll: 0,
+            search_scanned: 0,
+            search_total: 0,
+            search_indexing: None,
+            search_waiting: false,
+            searcher: None,
+            search_index: SharedIndex::default(),
+            search_title_matches: 0,
+            root_path,
+            settings,
             settings_selected: 0,
             doc_search_query: String::new(),
             doc_search_matches: Vec::new(),
             doc_search_current: 0,
-            pending_g: false,
-        };
-
-        // Auto-select first markdown file if available
-        app.select_first_file();
-        app
+            doc_search_pattern: None,
+            search_error: None,
+            search_history: SearchHistory::load(),
+            history_pos: None,
+            history_draft: String::new(),
+            keys: KeySequence::default(),
+            command_line: String::new(),
+            command_completions: Vec::new(),
+            command_completion_index: None,
+            status_message: None,
+            should_quit: false,
+            show_ignored: false,
+            sidebar_filter: String::new(),
+            file_op: None,
+            file_op_input: String::new(),
+            git_status: GitStatuses::default(),
+            git_status_rx: None,
+            filter_saved_expansion: None,
+            watcher: None,
+            scanner: None,
+            scanned_dirs: HashMap::new(),
+            scan_errors: HashMap::new(),
+            documents: Vec::new(),
+            scan_documents: Vec::new(),
+            document_meta: HashMap::new(),
+            titles: TitleCache::default(),
+            from_stdin: false,
+            stdin: None,
+            stdin_text: String::new(),
+        }
     }
 
     fn select_first_file(&mut self) {
@@ -204,8 +450,8 @@ impl App {
         // First, look for README.md (case-insensitive) in the root
         for (idx, item) in items.iter().enumerate() {
             if !item.is_dir {
-                let name_lower = item.name.to_lowercase();
-                if name_lower == "readme.md" || name_lower == "readme.markdown" {
+                let stem = item.path.file_stem().unwrap_or_default().to_string_lossy().to_lowercase();
+                if stem == "readme" {
                     self.selected_index = idx;
                     self.load_file(&item.path.clone());
                     return;
@@ -263,6 +509,7 @@ impl App {
 
             if is_dir {
                 // Toggle directory expansion
+                self.load_dir(&path);
                 if let Some(node) = self.tree.find_by_index_mut(self.selected_index) {
                     node.toggle_expanded();
                 }
@@ -297,6 +544,8 @@ impl App {
             if let Some(item) = items.get(self.selected_index) {
                 if item.is_dir {
                     // Expand directory
+                    let path = item.path.clone();
+                    self.load_dir(&path);
                     if let Some(node) = self.tree.find_by_index_mut(self.selected_index) {
                         if !node.expanded {
                             node.toggle_expanded();
@@ -313,6 +562,9 @@ impl App {
     }
 
     pub fn focus_sidebar_or_collapse(&mut self) {
+        if self.from_stdin {
+            return;
+        }
         if self.focus == Focus::Content {
             self.focus = Focus::Sidebar;
         } else {
@@ -322,6 +574,9 @@ impl App {
     }
 
     pub fn toggle_focus(&mut self) {
+        if self.from_stdin {
+            return;
+        }
         self.focus = match self.focus {
             Focus::Sidebar => Focus::Content,
             Focus::Content => Focus::Sidebar,
@@ -379,240 +634,1873 @@ impl App {
         self.content_scroll = max_scroll;
     }
 
-    fn load_file(&mut self, path: &PathBuf) {
-        self.current_file = Some(path.clone());
-        self.content_scroll = 0;
-
-        match fs::read_to_string(path) {
-            Ok(content) => {
-                self.rendered_content = render_markdown(&content);
+    /// Apply a Normal mode motion, repeated or targeted by an optional count
+    pub fn apply_motion(&mut self, motion: Motion, count: Option<usize>) {
+        let times = count.unwrap_or(1).max(1);
+        match motion {
+            Motion::Down => (0..times).for_each(|_| self.next()),
+            Motion::Up => (0..times).for_each(|_| self.previous()),
+            Motion::Top | Motion::Bottom => match count {
+                Some(line) => self.go_to_line(line),
+                None if motion == Motion::Top => self.scroll_to_top(),
+                None => self.scroll_to_bottom(),
+            },
+            Motion::HalfPageUp => {
+                let half_page = (self.content_height / 2) as usize;
+                self.content_scroll = self.content_scroll.saturating_sub(half_page * times);
             }
-            Err(e) => {
-                self.rendered_content = vec![
-                    create_plain_line(&format!("Error reading file: {}", e)),
-                ];
+            Motion::HalfPageDown => {
+                let half_page = (self.content_height / 2) as usize;
+                let max_scroll = self.total_wrapped_lines().saturating_sub(self.content_height as usize);
+                self.content_scroll = (self.content_scroll + half_page * times).min(max_scroll);
+            }
+            Motion::PageUp => {
+                let page_size = self.content_height as usize;
+                self.content_scroll = self.content_scroll.saturating_sub(page_size * times);
             }
+            Motion::PageDown => {
+                let page_size = self.content_height as usize;
+                let max_scroll = self.total_wrapped_lines().saturating_sub(self.content_height as usize);
+                self.content_scroll = (self.content_scroll + page_size * times).min(max_scroll);
+            }
+            Motion::NextHeading => self.jump_to_heading(true, times),
+            Motion::PreviousHeading => self.jump_to_heading(false, times),
         }
     }
 
-    pub fn set_content_height(&mut self, height: u16) {
-        self.content_height = height.saturating_sub(2); // Account for borders
+    /// Go to a 1-based line: a tree row in the sidebar, a source line in the content
+    pub fn go_to_line(&mut self, line: usize) {
+        let idx = line.saturating_sub(1);
+        if self.focus == Focus::Sidebar {
+            let items = self.visible_items();
+            self.selected_index = idx.min(items.len().saturating_sub(1));
+        } else {
+            let rendered = self.rendered_line_of(idx).unwrap_or(0);
+            let max_scroll = self.total_wrapped_lines().saturating_sub(self.content_height as usize);
+            self.content_scroll = self.source_to_wrapped_index(rendered).min(max_scroll);
+        }
     }
 
-    pub fn enter_search_mode(&mut self) {
-        self.mode = AppMode::Search;
-        self.search_query.clear();
-        self.search_results.clear();
-        self.search_selected = 0;
+    /// Scroll the content so the `count`th heading after (or before) the top line is at the top
+    fn jump_to_heading(&mut self, forward: bool, count: usize) {
+        let mut current = self.wrapped_to_source_index(self.content_scroll);
+        for _ in 0..count {
+            let found = if forward {
+                (current + 1..self.rendered_content.len())
+                    .find(|&idx| self.rendered_content[idx].heading_level > 0)
+            } else {
+                (0..current)
+                    .rev()
+                    .find(|&idx| self.rendered_content[idx].heading_level > 0)
+            };
+            match found {
+                Some(idx) => current = idx,
+                None => break,
+            }
+        }
+        let max_scroll = self.total_wrapped_lines().saturating_sub(self.content_height as usize);
+        self.content_scroll = self.source_to_wrapped_index(current).min(max_scroll);
     }
 
-    pub fn exit_search_mode(&mut self) {
+    pub fn enter_command_mode(&mut self) {
+        self.mode = AppMode::Command;
+        self.command_line.clear();
+        self.command_completions.clear();
+        self.command_completion_index = None;
+    }
+
+    pub fn exit_command_mode(&mut self) {
         self.mode = AppMode::Normal;
-        self.search_query.clear();
-        self.search_results.clear();
-        self.search_selected = 0;
+        self.command_line.clear();
+        self.command_completions.clear();
+        self.command_completion_index = None;
     }
 
-    pub fn enter_settings_mode(&mut self) {
-        self.mode = AppMode::Settings;
-        self.settings_selected = 0;
+    pub fn command_add_char(&mut self, c: char) {
+        self.command_line.push(c);
+        self.command_completions.clear();
+        self.command_completion_index = None;
     }
 
-    pub fn exit_settings_mode(&mut self) {
-        self.mode = AppMode::Normal;
+    pub fn command_backspace(&mut self) {
+        if self.command_line.pop().is_none() {
+            // Backspace on an empty line closes it, like vim
+            self.exit_command_mode();
+            return;
+        }
+        self.command_completions.clear();
+        self.command_completion_index = None;
     }
 
-    pub fn settings_toggle_current(&mut self) {
-        match self.settings_selected {
-            0 => self.settings.show_line_numbers = !self.settings.show_line_numbers,
-            1 => {
-                self.settings.theme = match self.settings.theme {
-                    Theme::Dark => Theme::Light,
-                    Theme::Light => Theme::Dark,
-                };
-            }
-            2 => {
-                self.settings.wrap_width = self.settings.wrap_width.next();
+    /// Tab completion: fill in the longest common prefix, then cycle through candidates
+    pub fn command_complete(&mut self, backwards: bool) {
+        if self.command_completions.is_empty() {
+            self.command_completions =
+                command::complete(&self.command_line, &self.root_path, &self.settings.file_types);
+            match self.command_completions.len() {
+                0 => return,
+                1 => {
+                    self.command_line = self.command_completions.remove(0);
+                    return;
+                }
+                _ => {
+                    let prefix = common_prefix(&self.command_completions);
+                    if prefix.len() > self.command_line.len() {
+                        self.command_line = prefix;
+                        return;
+                    }
+                }
             }
-            _ => {}
         }
-        self.settings.save();
+
+        let len = self.command_completions.len();
+        let next = match (self.command_completion_index, backwards) {
+            (None, false) => 0,
+            (None, true) => len - 1,
+            (Some(i), false) => (i + 1) % len,
+            (Some(i), true) => (i + len - 1) % len,
+        };
+        self.command_completion_index = Some(next);
+        self.command_line = self.command_completions[next].clone();
     }
 
-    pub fn settings_next(&mut self) {
-        let max_settings = 2; // 0, 1, 2
-        if self.settings_selected < max_settings {
-            self.settings_selected += 1;
+    pub fn command_execute(&mut self) {
+        let line = std::mem::take(&mut self.command_line);
+        self.exit_command_mode();
+
+        match command::parse(&line) {
+            Ok(ExCommand::GoToLine(line)) => self.go_to_line(line),
+            Ok(ExCommand::Open(path)) => self.open_path(&path),
+            Ok(ExCommand::Set(option)) => {
+                match option {
+                    SetOption::LineNumbers(on) => self.settings.show_line_numbers = on,
+                    SetOption::Wrap(width) => self.settings.wrap_width = width,
+                    SetOption::Theme(theme) => self.settings.theme = theme,
+                }
+                self.settings.save();
+            }
+            Ok(ExCommand::Quit) => self.should_quit = true,
+            Err(message) if message.is_empty() => {}
+            Err(message) => self.status_message = Some(message),
         }
     }
 
-    pub fn settings_previous(&mut self) {
-        if self.settings_selected > 0 {
-            self.settings_selected -= 1;
+    /// Open a file given relative to the root (or absolute) and select it in the tree
+    pub fn open_path(&mut self, path: &str) {
+        let path = PathBuf::from(path);
+        let path = if path.is_absolute() { path } else { self.root_path.join(path) };
+
+        if !path.is_file() {
+            self.status_message = Some(format!("open: no such file: {}", path.display()));
+            return;
         }
-    }
 
-    pub fn enter_doc_search_mode(&mut self) {
-        self.mode = AppMode::DocumentSearch;
-        self.doc_search_query.clear();
-        self.doc_search_matches.clear();
-        self.doc_search_current = 0;
+        self.load_ancestors(&path);
+        if let Some(idx) = self.tree.reveal(&path) {
+            self.selected_index = idx;
+        }
+        self.load_file(&path);
+        self.focus = Focus::Content;
     }
 
-    pub fn exit_doc_search_mode(&mut self) {
-        self.mode = AppMode::Normal;
-        self.doc_search_query.clear();
-        self.doc_search_matches.clear();
-        self.doc_search_current = 0;
+    /// Whether the background scan is still running
+    pub fn is_scanning(&self) -> bool {
+        self.scanner.is_some()
     }
 
-    pub fn doc_search_add_char(&mut self, c: char) {
-        self.doc_search_query.push(c);
-        self.perform_doc_search();
+    /// Every document known so far, for searching and filtering
+    fn all_documents(&self) -> &[PathBuf] {
+        if self.documents.is_empty() {
+            &self.scan_documents
+        } else {
+            &self.documents
+        }
     }
 
-    pub fn doc_search_backspace(&mut self) {
-        self.doc_search_query.pop();
-        self.perform_doc_search();
+    /// (Re)start the background scan of the whole tree
+    fn start_scan(&mut self) {
+        let options = self.settings.tree_options(self.show_ignored);
+        self.scanner = Some(Scanner::start(&self.root_path, options));
+        self.scanned_dirs.clear();
+        self.scan_errors.clear();
+        self.scan_documents.clear();
+        self.document_meta.clear();
     }
 
-    pub fn doc_search_next(&mut self) {
-        if !self.doc_search_matches.is_empty() {
-            self.doc_search_current = (self.doc_search_current + 1) % self.doc_search_matches.len();
-            self.jump_to_current_match();
+    /// Apply results from the background scan. Returns true if anything changed.
+    pub fn process_scan_events(&mut self) -> bool {
+        let events = match self.scanner {
+            Some(ref scanner) => scanner.poll_events(),
+            None => return false,
+        };
+        if events.is_empty() {
+            return false;
         }
-    }
 
-    pub fn doc_search_previous(&mut self) {
-        if !self.doc_search_matches.is_empty() {
-            if self.doc_search_current == 0 {
-                self.doc_search_current = self.doc_search_matches.len() - 1;
-            } else {
-                self.doc_search_current -= 1;
+        let selected_path = self.selected_path();
+        let mut found_documents = false;
+        for event in events {
+            match event {
+                ScanEvent::Document(path, meta) => {
+                    self.document_meta.insert(path.clone(), meta);
+                    self.scan_documents.push(path);
+                    found_documents = true;
+                }
+                ScanEvent::Dir(path, meta) => self.apply_dir_scan(path, meta),
+                ScanEvent::Error(path, message) => self.apply_scan_error(path, message),
+                ScanEvent::Finished => {
+                    self.scanner = None;
+                    self.documents = std::mem::take(&mut self.scan_documents);
+                }
             }
-            self.jump_to_current_match();
         }
-    }
-
-    fn perform_doc_search(&mut self) {
-        self.doc_search_matches.clear();
-        self.doc_search_current = 0;
 
-        if self.doc_search_query.is_empty() {
-            return;
+        if found_documents && !self.sidebar_filter.is_empty() {
+            // Matches may live in directories that have not been listed yet
+            self.update_filter();
+        } else if let Some(path) = selected_path {
+            let items_len = self.visible_items().len();
+            self.selected_index = path
+                .ancestors()
+                .find_map(|p| self.tree.visible_index_of(p))
+                .unwrap_or(self.selected_index)
+                .min(items_len.saturating_sub(1));
         }
+        true
+    }
 
-        let query_lower = self.doc_search_query.to_lowercase();
+    fn apply_scan_error(&mut self, path: PathBuf, message: String) {
+        // Keep the listing's own explanation if it already has one
+        if let Some(node) = self.tree.find_mut(&path) {
+            node.scanning = false;
+            node.error.get_or_insert_with(|| message.clone());
+        }
+        self.scan_errors.insert(path, message);
+    }
 
-        for (idx, line) in self.rendered_content.iter().enumerate() {
-            let line_text: String = line.segments.iter().map(|seg| {
-                match seg {
-                    TextSegment::Plain(s) => s.clone(),
-                    TextSegment::Code(s) => s.clone(),
-                    TextSegment::Link { text, .. } => text.clone(),
-                    TextSegment::Emphasis(s) => s.clone(),
-                    TextSegment::Strong(s) => s.clone(),
+    /// Drop a directory that turned out to hold no documents, or fill in its metadata
+    fn apply_dir_scan(&mut self, path: PathBuf, meta: Option<NodeMeta>) {
+        match meta {
+            None if path != self.root_path => {
+                self.tree.remove(&path);
+            }
+            None => {}
+            Some(ref meta) => {
+                if let Some(node) = self.tree.find_mut(&path) {
+                    node.scanning = false;
+                    node.meta = meta.clone();
+                }
+                // Directory totals may change its position when sorting by time or size
+                if let Some(parent) = path.parent().and_then(|p| self.tree.find_mut(p)) {
+                    sort_children(parent, self.settings.sort_mode);
                 }
-            }).collect();
-
-            if line_text.to_lowercase().contains(&query_lower) {
-                self.doc_search_matches.push(idx);
             }
         }
+        self.scanned_dirs.insert(path, meta);
+    }
 
-        // Jump to first match if found
-        if !self.doc_search_matches.is_empty() {
-            self.jump_to_current_match();
+    /// List a loaded directory again after entries in it changed, keeping
+    /// what was loaded below the children that are still there
+    fn reload_dir(&mut self, path: &Path) {
+        let Some(node) = self.tree.find_mut(path) else {
+            return;
+        };
+        if !node.loaded {
+            return;
         }
-    }
+        let mut previous: HashMap<PathBuf, TreeNode> =
+            std::mem::take(&mut node.children).into_iter().map(|c| (c.path.clone(), c)).collect();
+        node.loaded = false;
+        node.error = None;
+        self.load_dir(path);
 
-    fn jump_to_current_match(&mut self) {
-        if let Some(&line_idx) = self.doc_search_matches.get(self.doc_search_current) {
-            // Calculate wrapped line index from source line index
-            let wrapped_idx = self.source_to_wrapped_index(line_idx);
-            // Scroll to show the match, centered if possible
-            let half_height = (self.content_height / 2) as usize;
-            self.content_scroll = wrapped_idx.saturating_sub(half_height);
+        let Some(node) = self.tree.find_mut(path) else {
+            return;
+        };
+        for child in node.children.iter_mut().filter(|c| c.is_dir) {
+            if let Some(old) = previous.remove(&child.path).filter(|old| old.is_dir) {
+                child.children = old.children;
+                child.loaded = old.loaded;
+                child.expanded = old.expanded;
+            }
         }
     }
 
-    /// Convert a source line index to its wrapped line index
-    fn source_to_wrapped_index(&self, source_idx: usize) -> usize {
-        let max_width = self.settings.wrap_width.to_usize();
-        let mut wrapped_idx = 0;
+    /// List a directory's children the first time it is needed
+    fn load_dir(&mut self, path: &Path) {
+        let options = self.settings.tree_options(self.show_ignored);
+        let terms = self.filter_terms();
+        let Some(node) = self.tree.find_mut(path) else {
+            return;
+        };
+        if !node.is_dir || node.loaded {
+            return;
+        }
 
-        for (idx, line) in self.rendered_content.iter().enumerate() {
-            if idx == source_idx {
-                return wrapped_idx;
+        node.loaded = true;
+        let mut children = match list_dir(&node.path, node.depth, node.ignored, &options) {
+            Ok(children) => children,
+            Err(error) => {
+                node.error = Some(error);
+                return;
+            }
+        };
+        // Directories the scan has already reached are known to be empty or not
+        for child in &mut children {
+            if let Some(error) = self.scan_errors.get(&child.path) {
+                child.error.get_or_insert_with(|| error.clone());
+                child.scanning = false;
             }
-            // Count how many wrapped lines this source line produces
-            wrapped_idx += self.count_wrapped_lines(line, max_width);
         }
-
-        wrapped_idx
-    }
-
-    /// Count how many wrapped lines a single RenderedLine produces
-    fn count_wrapped_lines(&self, line: &RenderedLine, max_width: Option<usize>) -> usize {
-        if line.segments.is_empty() {
-            return 1;
+        children.retain_mut(|child| match self.scanned_dirs.get(&child.path) {
+            Some(None) => false,
+            Some(Some(meta)) => {
+                child.scanning = false;
+                child.meta = meta.clone();
+                true
+            }
+            None => true,
+        });
+
+        node.children = children;
+        let show_titles = self.settings.sidebar_label == SidebarLabel::Title;
+        set_titles(node, show_titles, &options, &mut self.titles);
+        if !terms.is_empty() {
+            node.apply_filter(&self.root_path, &terms);
         }
+    }
 
-        // Don't wrap table rows or separators
-        if line.is_table_row || line.is_table_separator {
-            return 1;
+    /// List every directory between the root and `path`
+    fn load_ancestors(&mut self, path: &Path) {
+        let mut ancestors: Vec<PathBuf> = path
+            .ancestors()
+            .skip(1)
+            .take_while(|p| p.starts_with(&self.root_path))
+            .map(|p| p.to_path_buf())
+            .collect();
+        ancestors.reverse();
+        for dir in ancestors {
+            self.load_dir(&dir);
         }
+    }
 
-        // If no wrapping, return 1
-        let max_width = match max_width {
-            Some(w) => w,
-            None => return 1,
+    /// Apply filesystem changes reported by the watcher. Returns true if anything changed.
+    pub fn process_fs_changes(&mut self) -> bool {
+        let Some(ref mut watcher) = self.watcher else {
+            return false;
         };
-
-        // Get the full text content to check length
-        let full_text: String = line.segments.iter().map(|seg| {
-            match seg {
-                TextSegment::Plain(s) => s.clone(),
-                TextSegment::Code(s) => format!("`{}`", s),
-                TextSegment::Link { text, .. } => text.clone(),
-                TextSegment::Emphasis(s) => s.clone(),
-                TextSegment::Strong(s) => s.clone(),
+        let changes = watcher.poll_changes(self.show_ignored);
+        if changes.is_empty() {
+            return false;
+        }
+        self.refresh_git_status();
+        self.search_index.mark_stale();
+
+        // Follow the open document if it was renamed (but not if an editor
+        // merely moved it aside to write a new copy in its place)
+        if let Some(ref current) = self.current_file {
+            let renamed_to = changes
+                .renames
+                .iter()
+                .find(|(from, _)| from == current && !from.exists())
+                .map(|(_, to)| to.clone());
+            if let Some(to) = renamed_to {
+                self.current_file = Some(to);
             }
-        }).collect();
+        }
 
-        // If line fits, no wrapping
-        if full_text.len() <= max_width {
-            return 1;
+        for path in changes.modified.iter().chain(changes.renames.iter().flat_map(|(from, to)| [from, to])) {
+            self.titles.invalidate(path);
         }
 
-        // Count wrapped lines
-        let mut count = 0;
-        let mut current_len = 0;
+        // Sorting by time or size, the metadata columns and titles go stale on any write
+        let uses_metadata = !matches!(self.settings.sort_mode, SortMode::Name | SortMode::Natural)
+            || self.settings.show_modified_column
+            || self.settings.show_word_count_column
+            || self.settings.sidebar_label == SidebarLabel::Title;
+        if changes.rules_changed || changes.structure_changed || (uses_metadata && !changes.modified.is_empty()) {
+            self.apply_tree_changes(&changes);
+        }
 
-        for word in full_text.split_whitespace() {
-            if current_len == 0 {
-                current_len = word.len();
-            } else if current_len + 1 + word.len() <= max_width {
-                current_len += 1 + word.len();
-            } else {
-                count += 1;
-                current_len = word.len();
+        if let Some(current) = self.current_file.clone() {
+            if changes.touches(&current) || changes.structure_changed || changes.rules_changed {
+                if current.exists() {
+                    self.reload_current_file();
+                } else {
+                    let name = current.file_name().unwrap_or_default().to_string_lossy();
+                    self.status_message = Some(format!("{} was deleted", name));
+                }
             }
         }
 
-        if current_len > 0 {
-            count += 1;
+        true
+    }
+
+    /// Re-run `git status` in the background; the result is picked up by `process_git_status`
+    fn refresh_git_status(&mut self) {
+        self.git_status_rx = Some(git::load_in_background(&self.root_path));
+    }
+
+    /// Apply a finished `git status` run. Returns true if the decorations changed.
+    pub fn process_git_status(&mut self) -> bool {
+        let Some(ref rx) = self.git_status_rx else {
+            return false;
+        };
+        match rx.try_recv() {
+            Ok(statuses) => {
+                self.git_status = statuses.unwrap_or_default();
+                self.git_status_rx = None;
+                true
+            }
+            Err(TryRecvError::Empty) => false,
+            Err(TryRecvError::Disconnected) => {
+                self.git_status_rx = None;
+                false
+            }
         }
+    }
 
-        if count == 0 {
-            1
+    /// Apply changes made on disk (or by a file operation) to the tree: entry
+    /// by entry, or by rebuilding it when ignore rules changed or the scan
+    /// that lists every document is still running
+    fn apply_tree_changes(&mut self, changes: &FsChanges) {
+        if changes.rules_changed || self.is_scanning() {
+            self.refresh_tree(changes);
         } else {
-            count
+            self.update_tree(changes);
         }
     }
 
-    /// Get total number of wrapped lines
-    pub fn total_wrapped_lines(&self) -> usize {
+    /// Rebuild the tree from disk, keeping expanded directories and the selection
+    fn refresh_tree(&mut self, changes: &FsChanges) {
+        let (selected_path, expanded) = self.tree_state(changes);
+        self.start_scan();
+        self.tree = load_root(&self.root_path, &self.settings.tree_options(self.show_ignored));
+        let root = self.root_path.clone();
+        self.fill_titles(&root);
+        self.restore_tree_state(selected_path, &expanded);
+    }
+
+    /// Bring the documents and the tree up to date with the entries in
+    /// `changes`, listing again only the directories they are in. New
+    /// directories are scanned on their own, and directory totals are added
+    /// up again from the documents' metadata, without reading anything else.
+    fn update_tree(&mut self, changes: &FsChanges) {
+        let (selected_path, expanded) = self.tree_state(changes);
+        let options = self.settings.tree_options(self.show_ignored);
+        let load_contents = options.load_contents || options.sort_mode == SortMode::FrontMatter;
+        let show_titles = self.settings.sidebar_label == SidebarLabel::Title;
+
+        let renamed = changes.renames.iter().flat_map(|(from, to)| [from, to]);
+        let mut relist: HashSet<PathBuf> = HashSet::new(); // Directories whose entries changed
+        let mut totals: HashMap<PathBuf, Option<NodeMeta>> = HashMap::new(); // Directories to add up again
+        for path in changes.modified.iter().chain(renamed) {
+            if !path.starts_with(&self.root_path) || *path == self.root_path {
+                continue;
+            }
+            let is_file = path.is_file();
+            let is_dir = path.is_dir();
+            let known_file = self.document_meta.contains_key(path);
+            let known_dir = self.scanned_dirs.contains_key(path);
+
+            if known_file && is_file {
+                // Written in place: only its metadata and title changed
+                let meta = file_meta(path, load_contents);
+                let title = if show_titles { self.titles.get(path) } else { None };
+                if let Some(node) = self.tree.find_mut(path) {
+                    node.meta = meta.clone();
+                    node.title = title;
+                }
+                if let Some(parent) = path.parent().and_then(|p| self.tree.find_mut(p)) {
+                    sort_children(parent, options.sort_mode);
+                }
+                self.document_meta.insert(path.clone(), meta);
+            } else if known_dir && is_dir {
+                continue; // Changes to its entries are reported for them
+            } else if known_file || known_dir || is_dir || (is_file && options.file_types.is_document(path)) {
+                self.forget_entries(path);
+                if is_dir {
+                    for event in scanner::scan_now(path, &options) {
+                        match event {
+                            ScanEvent::Document(path, meta) => self.add_document(path, meta),
+                            ScanEvent::Dir(path, meta) => self.apply_dir_scan(path, meta),
+                            ScanEvent::Error(path, message) => self.apply_scan_error(path, message),
+                            ScanEvent::Finished => {}
+                        }
+                    }
+                } else if is_file {
+                    self.add_document(path.clone(), file_meta(path, load_contents));
+                }
+                relist.extend(path.parent().map(Path::to_path_buf));
+            } else {
+                continue; // Not something the sidebar shows
+            }
+
+            let ancestors = path.ancestors().skip(1).take_while(|dir| dir.starts_with(&self.root_path));
+            totals.extend(ancestors.map(|dir| (dir.to_path_buf(), None)));
+        }
+
+        // Add up the totals from every document (and unreadable entry) below them
+        let entries = self
+            .document_meta
+            .iter()
+            .map(|(path, meta)| (path, meta.clone()))
+            .chain(self.scan_errors.keys().map(|path| (path, NodeMeta::default())));
+        for (path, meta) in entries {
+            for dir in path.ancestors().skip(1).take_while(|dir| dir.starts_with(&self.root_path)) {
+                if let Some(total) = totals.get_mut(dir) {
+                    total.get_or_insert_with(NodeMeta::default).add(&meta);
+                }
+            }
+        }
+        for (dir, meta) in totals {
+            self.apply_dir_scan(dir, meta);
+        }
+
+        // List each changed directory again, or the nearest one above it in
+        // the tree, which brings back directories that were empty before
+        let mut listed = HashSet::new();
+        for dir in relist {
+            let nearest = dir.ancestors().find(|d| self.tree.find_mut(d).is_some()).map(Path::to_path_buf);
+            if let Some(nearest) = nearest.filter(|d| listed.insert(d.clone())) {
+                self.reload_dir(&nearest);
+            }
+        }
+        self.restore_tree_state(selected_path, &expanded);
+    }
+
+    /// Drop what the scan knew about `path` and everything below it
+    fn forget_entries(&mut self, path: &Path) {
+        self.documents.retain(|doc| !doc.starts_with(path));
+        self.document_meta.retain(|doc, _| !doc.starts_with(path));
+        self.scanned_dirs.retain(|dir, _| !dir.starts_with(path));
+        self.scan_errors.retain(|entry, _| !entry.starts_with(path));
+    }
+
+    fn add_document(&mut self, path: PathBuf, meta: NodeMeta) {
+        // Kept in the order the scan finds them, which is path order
+        if let Err(idx) = self.documents.binary_search(&path) {
+            self.documents.insert(idx, path.clone());
+        }
+        self.document_meta.insert(path, meta);
+    }
+
+    /// The selected entry and the expanded directories, followed across renames
+    fn tree_state(&self, changes: &FsChanges) -> (Option<PathBuf>, HashSet<PathBuf>) {
+        let selected_path = self
+            .visible_items()
+            .get(self.selected_index)
+            .map(|item| item.path.clone());
+
+        // Carry expansion state over renamed directories
+        let mut expanded = self.tree.expanded_paths();
+        for (from, to) in &changes.renames {
+            let moved: Vec<PathBuf> = expanded
+                .iter()
+                .filter(|p| p.starts_with(from))
+                .cloned()
+                .collect();
+            for path in moved {
+                expanded.remove(&path);
+                if let Ok(rel) = path.strip_prefix(from) {
+                    expanded.insert(to.join(rel));
+                }
+            }
+        }
+
+        let selected_path = selected_path.map(|path| {
+            changes
+                .renames
+                .iter()
+                .find_map(|(from, to)| path.strip_prefix(from).ok().map(|rel| to.join(rel)))
+                .unwrap_or(path)
+        });
+        (selected_path, expanded)
+    }
+
+    /// Expand the directories again, reapply the filter and select the entry
+    /// (or the one now in its place)
+    fn restore_tree_state(&mut self, selected_path: Option<PathBuf>, expanded: &HashSet<PathBuf>) {
+        for dir in expanded {
+            self.load_ancestors(dir);
+            self.load_dir(dir);
+        }
+        self.tree.restore_expanded(expanded);
+        if !self.sidebar_filter.is_empty() {
+            self.load_filter_matches(&self.filter_terms());
+            self.tree.apply_filter(&self.root_path, &self.filter_terms());
+        }
+
+        let items_len = self.visible_items().len();
+        self.selected_index = selected_path
+            .and_then(|path| self.tree.visible_index_of(&path))
+            .unwrap_or(self.selected_index)
+            .min(items_len.saturating_sub(1));
+    }
+
+    pub fn enter_filter_mode(&mut self) {
+        if self.from_stdin {
+            return;
+        }
+        self.mode = AppMode::Filter;
+        self.focus = Focus::Sidebar;
+        if self.filter_saved_expansion.is_none() {
+            self.filter_saved_expansion = Some(self.tree.expanded_paths());
+        }
+    }
+
+    /// Leave filter mode, keeping the filter applied for navigation
+    pub fn accept_filter(&mut self) {
+        self.mode = AppMode::Normal;
+        if self.sidebar_filter.is_empty() {
+            self.clear_filter();
+        }
+    }
+
+    /// Remove the filter and restore the expansion state from before it
+    pub fn clear_filter(&mut self) {
+        self.mode = AppMode::Normal;
+        let selected_path = self.selected_path();
+        self.sidebar_filter.clear();
+        self.tree.clear_filter();
+        if let Some(expanded) = self.filter_saved_expansion.take() {
+            self.tree.restore_expanded(&expanded);
+        }
+        // Select the entry again, or its nearest visible ancestor if it is collapsed away
+        if let Some(path) = selected_path {
+            let visible = path.ancestors().find_map(|p| self.tree.visible_index_of(p));
+            self.selected_index = visible.unwrap_or(0);
+        }
+    }
+
+    pub fn filter_add_char(&mut self, c: char) {
+        self.sidebar_filter.push(c);
+        self.update_filter();
+    }
+
+    pub fn filter_backspace(&mut self) {
+        self.sidebar_filter.pop();
+        self.update_filter();
+    }
+
+    /// Lowercased, whitespace-separated filter terms
+    pub fn filter_terms(&self) -> Vec<String> {
+        self.sidebar_filter
+            .to_lowercase()
+            .split_whitespace()
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    fn update_filter(&mut self) {
+        let selected_path = self.selected_path();
+        let terms = self.filter_terms();
+
+        self.tree.clear_filter();
+        if let Some(ref expanded) = self.filter_saved_expansion {
+            self.tree.restore_expanded(expanded);
+        }
+        if !terms.is_empty() {
+            self.load_filter_matches(&terms);
+            self.tree.apply_filter(&self.root_path, &terms);
+        }
+
+        // Stay on the same entry if it still matches, otherwise jump to the first match
+        let items = self.visible_items();
+        self.selected_index = selected_path
+            .and_then(|path| items.iter().position(|item| item.path == path && !item.is_dir))
+            .or_else(|| items.iter().position(|item| !item.is_dir))
+            .unwrap_or(0);
+    }
+
+    /// List the directories holding documents that match the filter
+    fn load_filter_matches(&mut self, terms: &[String]) {
+        let show_titles = self.settings.sidebar_label == SidebarLabel::Title;
+        let documents = self.all_documents().to_vec();
+        let matches: Vec<PathBuf> = documents
+            .into_iter()
+            .filter(|path| {
+                let title = if show_titles { self.titles.get(path) } else { None };
+                matches_terms(&self.root_path, path, title.as_deref(), terms)
+            })
+            .collect();
+        for path in matches {
+            self.load_ancestors(&path);
+        }
+    }
+
+    /// Directory new entries go into: the selected directory, or the selected file's
+    fn target_dir(&self) -> PathBuf {
+        let items = self.visible_items();
+        match items.get(self.selected_index) {
+            Some(node) if node.is_dir => node.path.clone(),
+            Some(node) => node.path.parent().unwrap_or(&self.root_path).to_path_buf(),
+            None => self.root_path.clone(),
+        }
+    }
+
+    /// Start a file operation prompt for the selected entry (or its directory)
+    pub fn begin_file_op(&mut self, op: FileOp) {
+        let selected = self.selected_path().filter(|path| *path != self.root_path);
+        self.file_op_input = match op {
+            FileOp::Rename(_) | FileOp::Move(_) | FileOp::Delete(_) if selected.is_none() => {
+                self.status_message = Some("Select a file or directory first".to_string());
+                return;
+            }
+            FileOp::Rename(ref path) => path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+            FileOp::Move(ref path) => path.strip_prefix(&self.root_path).unwrap_or(path).to_string_lossy().to_string(),
+            _ => String::new(),
+        };
+        self.file_op = Some(op);
+        self.mode = AppMode::FileOp;
+    }
+
+    pub fn begin_new_file(&mut self, dir: bool) {
+        let target = self.target_dir();
+        self.begin_file_op(if dir {
+            FileOp::NewDir { dir: target, template: None }
+        } else {
+            FileOp::NewFile { dir: target, template: None }
+        });
+    }
+
+    pub fn begin_rename(&mut self) {
+        let path = self.selected_path().unwrap_or_default();
+        self.begin_file_op(FileOp::Rename(path));
+    }
+
+    pub fn begin_move(&mut self) {
+        let path = self.selected_path().unwrap_or_default();
+        self.begin_file_op(FileOp::Move(path));
+    }
+
+    pub fn begin_delete(&mut self) {
+        let path = self.selected_path().unwrap_or_default();
+        self.begin_file_op(FileOp::Delete(path));
+    }
+
+    pub fn cancel_file_op(&mut self) {
+        self.file_op = None;
+        self.file_op_input.clear();
+        self.mode = AppMode::Normal;
+    }
+
+    pub fn file_op_add_char(&mut self, c: char) {
+        self.file_op_input.push(c);
+    }
+
+    pub fn file_op_backspace(&mut self) {
+        self.file_op_input.pop();
+    }
+
+    /// Step through the templates for a new file or directory (and back to none)
+    pub fn file_op_next_template(&mut self) {
+        let dirs = matches!(self.file_op, Some(FileOp::NewDir { .. }));
+        let count = fileops::templates(dirs).len();
+        if let Some(FileOp::NewFile { template, .. } | FileOp::NewDir { template, .. }) = self.file_op.as_mut() {
+            *template = match *template {
+                None if count > 0 => Some(0),
+                Some(idx) if idx + 1 < count => Some(idx + 1),
+                _ => None,
+            };
+        }
+    }
+
+    /// Text shown in the status bar for the pending file operation
+    pub fn file_op_prompt(&self) -> String {
+        let rel = |path: &Path| {
+            let rel = path.strip_prefix(&self.root_path).unwrap_or(path).to_string_lossy().to_string();
+            if rel.is_empty() { ".".to_string() } else { rel }
+        };
+        let template_name = |dirs: bool, template: &Option<usize>| {
+            template
+                .and_then(|idx| fileops::templates(dirs).get(idx).cloned())
+                .map(|t| t.file_name().unwrap_or_default().to_string_lossy().to_string())
+                .unwrap_or_else(|| "default".to_string())
+        };
+        match self.file_op {
+            Some(FileOp::NewFile { ref dir, ref template }) => {
+                format!("New file in {}/ [template: {}, Tab to change]:", rel(dir), template_name(false, template))
+            }
+            Some(FileOp::NewDir { ref dir, ref template }) => {
+                format!("New directory in {}/ [template: {}, Tab to change]:", rel(dir), template_name(true, template))
+            }
+            Some(FileOp::Rename(ref path)) => format!("Rename {} to:", rel(path)),
+            Some(FileOp::Move(ref path)) => format!("Move {} to:", rel(path)),
+            Some(FileOp::Delete(ref path)) if path.is_dir() => {
+                format!("Delete {}/ and everything in it? (y/n)", rel(path))
+            }
+            Some(FileOp::Delete(ref path)) => format!("Delete {}? (y/n)", rel(path)),
+            Some(FileOp::UpdateLinks { ref documents, .. }) => {
+                format!("Update links in {} document(s)? (y/n)", documents.len())
+            }
+            None => String::new(),
+        }
+    }
+
+    /// Carry out the pending file operation (Enter, or `y` for confirmations)
+    pub fn confirm_file_op(&mut self) {
+        let Some(op) = self.file_op.take() else {
+            return;
+        };
+        let input = std::mem::take(&mut self.file_op_input).trim().to_string();
+        self.mode = AppMode::Normal;
+
+        let result = match op {
+            FileOp::NewFile { .. } | FileOp::NewDir { .. } | FileOp::Rename(_) | FileOp::Move(_)
+                if input.is_empty() =>
+            {
+                return;
+            }
+            FileOp::NewFile { dir, template } => {
+                let Some(mut path) = self.target_in_root(&dir.join(&input)) else {
+                    return;
+                };
+                if path.extension().is_none() {
+                    path.set_extension("md");
+                }
+                let template = template.and_then(|idx| fileops::templates(false).get(idx).cloned());
+                fileops::create_file(&path, template.as_deref()).map(|_| {
+                    self.apply_tree_changes(&FsChanges::entry(&path));
+                    self.select_path(&path);
+                    self.load_file(&path);
+                    format!("Created {}", path.file_name().unwrap_or_default().to_string_lossy())
+                })
+            }
+            FileOp::NewDir { dir, template } => {
+                let Some(path) = self.target_in_root(&dir.join(&input)) else {
+                    return;
+                };
+                let template = template.and_then(|idx| fileops::templates(true).get(idx).cloned());
+                fileops::create_dir(&path, template.as_deref()).map(|_| {
+                    self.apply_tree_changes(&FsChanges::entry(&path));
+                    self.select_path(&path);
+                    format!("Created {}/", path.file_name().unwrap_or_default().to_string_lossy())
+                })
+            }
+            FileOp::Rename(from) => {
+                if input.contains('/') {
+                    self.status_message = Some("rename: use m to move to another directory".to_string());
+                    return;
+                }
+                let Some(to) = self.target_in_root(&from.with_file_name(&input)) else {
+                    return;
+                };
+                self.rename_entry(&from, &to)
+            }
+            FileOp::Move(from) => {
+                let Some(mut to) = self.target_in_root(&self.root_path.join(&input)) else {
+                    return;
+                };
+                // Moving onto a directory puts the entry inside it
+                if input.ends_with('/') || to.is_dir() {
+                    to = to.join(from.file_name().unwrap_or_default());
+                }
+                self.rename_entry(&from, &to)
+            }
+            FileOp::Delete(path) => fileops::delete(&path).map(|_| {
+                let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                self.apply_tree_changes(&FsChanges::entry(&path));
+                format!("Deleted {}", name)
+            }),
+            FileOp::UpdateLinks { from, to, documents } => {
+                let failed = documents
+                    .iter()
+                    .filter(|doc| fileops::rewrite_links(doc, &self.root_path, &from, &to).is_err())
+                    .count();
+                if self.current_file.as_ref().is_some_and(|current| documents.contains(current)) {
+                    self.reload_current_file();
+                }
+                Ok(match failed {
+                    0 => format!("Updated links in {} document(s)", documents.len()),
+                    n => format!("Could not update links in {} document(s)", n),
+                })
+            }
+        };
+
+        self.status_message = Some(match result {
+            Ok(message) => message,
+            Err(e) => format!("Error: {}", e),
+        });
+    }
+
+    /// Where a file operation's typed path leads, or None with an error
+    /// shown if it is outside the root directory
+    fn target_in_root(&mut self, path: &Path) -> Option<PathBuf> {
+        let target = fileops::within_root(&self.root_path, path);
+        if target.is_none() {
+            self.status_message = Some(format!(
+                "Error: {} is outside {}",
+                fileops::normalize(path).display(),
+                self.root_path.display()
+            ));
+        }
+        target
+    }
+
+    /// Rename or move an entry, then offer to fix links that pointed at it
+    fn rename_entry(&mut self, from: &Path, to: &Path) -> std::io::Result<String> {
+        fileops::rename(from, to)?;
+
+        if let Some(current) = self.current_file.clone() {
+            if let Ok(rest) = current.strip_prefix(from) {
+                self.current_file = Some(if rest.as_os_str().is_empty() { to.to_path_buf() } else { to.join(rest) });
+            }
+        }
+        let changes = FsChanges {
+            renames: vec![(from.to_path_buf(), to.to_path_buf())],
+            structure_changed: true,
+            ..FsChanges::default()
+        };
+        self.apply_tree_changes(&changes);
+        self.select_path(to);
+
+        let documents: Vec<PathBuf> = self.all_documents().iter().filter(|d| d.exists()).cloned().collect();
+        let documents = fileops::documents_linking_to(&documents, &self.root_path, from);
+        if !documents.is_empty() {
+            self.file_op = Some(FileOp::UpdateLinks {
+                from: from.to_path_buf(),
+                to: to.to_path_buf(),
+                documents,
+            });
+            self.mode = AppMode::FileOp;
+        }
+
+        let rel = to.strip_prefix(&self.root_path).unwrap_or(to);
+        Ok(format!("Moved to {}", rel.display()))
+    }
+
+    /// Reveal and select `path` in the sidebar
+    fn select_path(&mut self, path: &Path) {
+        self.load_ancestors(path);
+        if let Some(idx) = self.tree.reveal(path) {
+            self.selected_index = idx;
+        }
+    }
+
+    /// The open file and the 1-based source line at the top of the viewport
+    pub fn edit_target(&self) -> Option<(PathBuf, usize)> {
+        let path = self.current_file.clone()?;
+        let top = self.wrapped_to_source_index(self.content_scroll);
+        let line = self.rendered_content.get(top).map(|l| l.source_line).unwrap_or(0);
+        Some((path, line + 1))
+    }
+
+    /// Why the selected sidebar entry could not be read, if it couldn't
+    pub fn selected_error(&self) -> Option<(String, String)> {
+        if self.focus != Focus::Sidebar {
+            return None;
+        }
+        let items = self.visible_items();
+        let node = items.get(self.selected_index)?;
+        node.error.clone().map(|error| (node.name.clone(), error))
+    }
+
+    fn selected_path(&self) -> Option<PathBuf> {
+        self.visible_items()
+            .get(self.selected_index)
+            .map(|item| item.path.clone())
+    }
+
+    /// Toggle showing hidden and ignored entries in the sidebar
+    pub fn toggle_show_ignored(&mut self) {
+        self.show_ignored = !self.show_ignored;
+        self.refresh_tree(&FsChanges::default());
+    }
+
+    /// Re-render the open document, keeping the reading position on the same section
+    pub fn reload_current_file(&mut self) {
+        let Some(path) = self.current_file.clone() else {
+            return;
+        };
+        let Ok(content) = fs::read_to_string(&path) else {
+            return;
+        };
+        self.rerender(&content, self.kind_of(&path));
+    }
+
+    /// Append text that arrived on stdin and re-render it. Returns true if anything arrived.
+    pub fn process_stdin(&mut self) -> bool {
+        let Some(ref rx) = self.stdin else {
+            return false;
+        };
+        let mut received = false;
+        loop {
+            match rx.try_recv() {
+                Ok(text) => {
+                    self.stdin_text.push_str(&text);
+                    received = true;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.stdin = None;
+                    received = true; // The title stops showing "reading…"
+                    break;
+                }
+            }
+        }
+        if received && self.current_file.is_none() {
+            let text = std::mem::take(&mut self.stdin_text);
+            self.rerender(&text, DocKind::Markdown);
+            self.stdin_text = text;
+        }
+        received
+    }
+
+    /// Whether more text may still arrive on stdin
+    pub fn is_reading_stdin(&self) -> bool {
+        self.stdin.is_some()
+    }
+
+    /// Replace the rendered document, keeping the reading position on the same
+    /// section and the search matches up to date
+    fn rerender(&mut self, content: &str, kind: DocKind) {
+        let anchor = self.scroll_anchor();
+        self.rendered_content = render_document(content, kind);
+        self.restore_scroll_anchor(anchor);
+
+        if !self.doc_search_query.is_empty() {
+            let current = self.doc_search_current;
+            let scroll = self.content_scroll;
+            self.perform_doc_search();
+            self.doc_search_current = current.min(self.doc_search_matches.len().saturating_sub(1));
+            self.content_scroll = scroll;
+        }
+    }
+
+    fn scroll_anchor(&self) -> ScrollAnchor {
+        let top = self.wrapped_to_source_index(self.content_scroll);
+        let heading_idx = (0..=top)
+            .rev()
+            .find(|&idx| self.rendered_content.get(idx).map(|l| l.heading_level > 0).unwrap_or(false));
+
+        let heading = heading_idx.map(|idx| {
+            let text = self.rendered_content[idx].to_plain_string();
+            let occurrence = self.rendered_content[..idx]
+                .iter()
+                .filter(|l| l.heading_level > 0 && l.to_plain_string() == text)
+                .count();
+            (text, occurrence)
+        });
+        let offset = heading_idx
+            .map(|idx| self.content_scroll.saturating_sub(self.source_to_wrapped_index(idx)))
+            .unwrap_or(self.content_scroll);
+
+        ScrollAnchor {
+            heading,
+            offset,
+            fallback: self.content_scroll,
+        }
+    }
+
+    fn restore_scroll_anchor(&mut self, anchor: ScrollAnchor) {
+        let heading_idx = anchor.heading.and_then(|(text, occurrence)| {
+            self.rendered_content
+                .iter()
+                .enumerate()
+                .filter(|(_, l)| l.heading_level > 0 && l.to_plain_string() == text)
+                .nth(occurrence)
+                .map(|(idx, _)| idx)
+        });
+
+        let scroll = match heading_idx {
+            Some(idx) => self.source_to_wrapped_index(idx) + anchor.offset,
+            None => anchor.fallback,
+        };
+        let max_scroll = self.total_wrapped_lines().saturating_sub(self.content_height as usize);
+        self.content_scroll = scroll.min(max_scroll);
+    }
+
+    fn load_file(&mut self, path: &PathBuf) {
+        self.current_file = Some(path.clone());
+        self.content_scroll = 0;
+
+        match fs::read_to_string(path) {
+            Ok(content) => {
+                self.rendered_content = render_document(&content, self.kind_of(path));
+            }
+            Err(e) => {
+                self.rendered_content = vec![
+                    create_plain_line(&format!("Error reading file: {}", e)),
+                ];
+            }
+        }
+
+        // A search kept from the previous document carries over, from the top
+        if !self.doc_search_query.is_empty() {
+            self.perform_doc_search();
+            self.content_scroll = 0;
+        }
+    }
+
+    /// How to render a file; anything unrecognised (e.g. opened with `:open`) is Markdown
+    fn kind_of(&self, path: &Path) -> DocKind {
+        self.settings.file_types.kind_of(path).unwrap_or(DocKind::Markdown)
+    }
+
+    /// Whether the sidebar is part of the layout. A hidden or collapsed
+    /// sidebar still slides in while it has focus.
+    pub fn sidebar_shown(&self) -> bool {
+        self.mode != AppMode::Select
+            && !self.from_stdin
+            && (self.focus == Focus::Sidebar || (self.settings.show_sidebar && !self.sidebar_collapsed()))
+    }
+
+    /// Whether the terminal is too narrow to keep the sidebar open
+    fn sidebar_collapsed(&self) -> bool {
+        self.layout_width < self.settings.sidebar_collapse_below
+    }
+
+    /// Sidebar width in columns, leaving the content pane usable
+    pub fn sidebar_width(&self) -> u16 {
+        let width = (self.layout_width as u32 * self.settings.sidebar_width as u32 / 100) as u16;
+        let max = self.layout_width.saturating_sub(MIN_CONTENT_WIDTH).max(MIN_SIDEBAR_WIDTH);
+        width.clamp(MIN_SIDEBAR_WIDTH, max)
+    }
+
+    pub fn toggle_sidebar(&mut self) {
+        if self.from_stdin {
+            return;
+        }
+        if self.sidebar_shown() {
+            // A collapsed sidebar is only showing because it has focus
+            if !self.sidebar_collapsed() {
+                self.settings.show_sidebar = false;
+            }
+            self.focus = Focus::Content;
+        } else if self.sidebar_collapsed() {
+            self.focus = Focus::Sidebar;
+        } else {
+            self.settings.show_sidebar = true;
+        }
+        self.settings.save();
+    }
+
+    /// Widen (or with a negative `delta`, narrow) the sidebar by `delta` percent
+    pub fn resize_sidebar(&mut self, delta: i16) {
+        let width = self.settings.sidebar_width as i16 + delta;
+        self.set_sidebar_width(width.max(0) as u16);
+        self.settings.save();
+        self.status_message = Some(format!("Sidebar width: {}%", self.settings.sidebar_width));
+    }
+
+    fn set_sidebar_width(&mut self, percent: u16) {
+        self.settings.sidebar_width = percent.clamp(10, 80);
+    }
+
+    /// Start dragging if the click is on the border between sidebar and content
+    fn start_sidebar_resize(&mut self, column: u16, row: u16) {
+        let border = self.sidebar_width();
+        self.resizing_sidebar = self.areas.sidebar.is_some_and(|area| (area.y..area.bottom()).contains(&row))
+            && (border.saturating_sub(1)..=border).contains(&column);
+    }
+
+    /// Move the sidebar border to `column` while dragging
+    fn drag_sidebar(&mut self, column: u16) {
+        if self.layout_width > 0 {
+            let percent = ((column as u32 + 1) * 100 + self.layout_width as u32 / 2) / self.layout_width as u32;
+            self.set_sidebar_width(percent as u16);
+        }
+    }
+
+    fn finish_sidebar_resize(&mut self) {
+        self.resizing_sidebar = false;
+        self.settings.save();
+    }
+
+    /// Scroll the sidebar so the selection is visible, when the selection or
+    /// the height changed since the last draw. Wheel scrolling in between
+    /// leaves the selection where it is.
+    pub fn fit_sidebar_scroll(&mut self, height: usize) {
+        if self.sidebar_fitted != Some((self.selected_index, height)) {
+            if self.selected_index < self.sidebar_scroll {
+                self.sidebar_scroll = self.selected_index;
+            } else if height > 0 && self.selected_index >= self.sidebar_scroll + height {
+                self.sidebar_scroll = self.selected_index + 1 - height;
+            }
+            self.sidebar_fitted = Some((self.selected_index, height));
+        }
+        let len = self.visible_items().len();
+        self.sidebar_scroll = self.sidebar_scroll.min(len.saturating_sub(height));
+    }
+
+    /// Act on a mouse event in whichever pane or overlay it falls in
+    pub fn handle_mouse(&mut self, mouse: MouseEvent) {
+        match self.mode {
+            AppMode::Search | AppMode::Settings => return self.handle_overlay_mouse(mouse),
+            AppMode::About => {
+                if matches!(mouse.kind, MouseEventKind::Down(_)) {
+                    self.mode = AppMode::Normal;
+                }
+                return;
+            }
+            // Leave the terminal's own selection alone in select mode
+            AppMode::Select | AppMode::Command | AppMode::FileOp => return,
+            _ => {}
+        }
+
+        let (column, row) = (mouse.column, mouse.row);
+        let at = Position::new(column, row);
+        let in_sidebar = self.areas.sidebar.is_some_and(|area| area.contains(at));
+        match mouse.kind {
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+                let down = mouse.kind == MouseEventKind::ScrollDown;
+                if in_sidebar {
+                    self.scroll_sidebar(down);
+                } else if down {
+                    let max_scroll = self.total_wrapped_lines().saturating_sub(self.content_height as usize);
+                    self.content_scroll = (self.content_scroll + 3).min(max_scroll);
+                } else {
+                    self.content_scroll = self.content_scroll.saturating_sub(3);
+                }
+            }
+            MouseEventKind::Down(MouseButton::Left) => {
+                let double = self.register_click(column, row);
+                self.start_sidebar_resize(column, row);
+                if self.resizing_sidebar {
+                    return;
+                }
+                if self.areas.scrollbar.is_some_and(|area| area.contains(at)) {
+                    self.dragging_scrollbar = true;
+                    self.scroll_to_scrollbar_row(row);
+                } else if in_sidebar {
+                    self.click_sidebar(row, double);
+                } else if self.areas.content.contains(at) {
+                    self.click_content(column, row);
+                }
+            }
+            MouseEventKind::Drag(MouseButton::Left) if self.resizing_sidebar => self.drag_sidebar(column),
+            MouseEventKind::Drag(MouseButton::Left) if self.dragging_scrollbar => self.scroll_to_scrollbar_row(row),
+            MouseEventKind::Up(MouseButton::Left) => {
+                if self.resizing_sidebar {
+                    self.finish_sidebar_resize();
+                }
+                self.dragging_scrollbar = false;
+            }
+            _ => {}
+        }
+    }
+
+    /// Clicks and wheel scrolling in the search and settings overlays
+    fn handle_overlay_mouse(&mut self, mouse: MouseEvent) {
+        let search = self.mode == AppMode::Search;
+        match mouse.kind {
+            MouseEventKind::ScrollDown if search => self.search_next(),
+            MouseEventKind::ScrollUp if search => self.search_previous(),
+            MouseEventKind::ScrollDown => self.settings_next(),
+            MouseEventKind::ScrollUp => self.settings_previous(),
+            MouseEventKind::Down(MouseButton::Left) => {
+                let Some(list) = self.areas.overlay_list else { return };
+                if !list.contains(Position::new(mouse.column, mouse.row)) {
+                    return;
+                }
+                let idx = (mouse.row - list.y) as usize;
+                if search && self.search_scroll + idx < self.search_rows {
+                    self.search_selected = self.search_scroll + idx;
+                    self.search_select();
+                } else if !search && idx <= LAST_SETTING {
+                    self.settings_selected = idx;
+                    self.settings_toggle_current();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Record a click, returning true if it completes a double-click on the same cell
+    fn register_click(&mut self, column: u16, row: u16) -> bool {
+        let now = Instant::now();
+        let double = self.last_click.is_some_and(|(at, c, r)| {
+            c == column && r == row && now.duration_since(at) < DOUBLE_CLICK
+        });
+        self.last_click = if double { None } else { Some((now, column, row)) };
+        double
+    }
+
+    fn scroll_sidebar(&mut self, down: bool) {
+        let height = self.areas.sidebar.map(|area| area.height.saturating_sub(2) as usize).unwrap_or(0);
+        let len = self.visible_items().len();
+        self.sidebar_scroll = if down {
+            (self.sidebar_scroll + 3).min(len.saturating_sub(height))
+        } else {
+            self.sidebar_scroll.saturating_sub(3)
+        };
+    }
+
+    /// Select the clicked sidebar row; a double-click opens it like Enter
+    fn click_sidebar(&mut self, row: u16, double: bool) {
+        let Some(area) = self.areas.sidebar else { return };
+        if row <= area.y || row + 1 >= area.bottom() {
+            return; // On the border
+        }
+        let idx = self.sidebar_scroll + (row - area.y - 1) as usize;
+        if idx >= self.visible_items().len() {
+            return;
+        }
+        self.focus = Focus::Sidebar;
+        self.selected_index = idx;
+        if double {
+            self.toggle_or_select();
+        }
+    }
+
+    /// Follow the link under a click in the document, if there is one
+    fn click_content(&mut self, column: u16, row: u16) {
+        if !self.from_stdin {
+            self.focus = Focus::Content;
+        }
+        let area = self.areas.content;
+        let wrapped_idx = self.content_scroll + (row - area.y) as usize;
+        let gutter = if self.settings.show_line_numbers {
+            self.rendered_content.len().to_string().len() + 3 // "12 │ "
+        } else {
+            0
+        };
+        let Some(column) = ((column - area.x) as usize).checked_sub(gutter) else { return };
+        if let Some(url) = self.link_at(wrapped_idx, column) {
+            self.follow_link(&url);
+        }
+    }
+
+    /// The URL of the link shown at `column` of wrapped line `wrapped_idx`
+    fn link_at(&self, wrapped_idx: usize, column: usize) -> Option<String> {
+        let max_width = self.settings.wrap_width.to_usize();
+        let mut first = 0;
+        let (line, row) = self.rendered_content.iter().find_map(|line| {
+            let count = self.count_wrapped_lines(line, max_width);
+            first += count;
+            (wrapped_idx < first).then(|| (line, wrapped_idx + count - first))
+        })?;
+
+        // The line's text as drawn, and where its links are in it
+        let mut text = String::new();
+        let mut links = Vec::new();
+        for segment in &line.segments {
+            let start = text.len();
+            match segment {
+                TextSegment::Code(s) => text.push_str(&format!("`{}`", s)),
+                TextSegment::Link { text: link_text, url } => {
+                    text.push_str(link_text);
+                    links.push((start..text.len(), url));
+                }
+                TextSegment::Plain(s) | TextSegment::Emphasis(s) | TextSegment::Strong(s) => text.push_str(s),
+            }
+        }
+        if links.is_empty() {
+            return None;
+        }
+
+        let offset = match max_width {
+            Some(width) if text.len() > width && !line.is_table_row && !line.is_table_separator => {
+                wrapped_offset(&text, width, row, column)?
+            }
+            _ => text.char_indices().nth(column)?.0,
+        };
+        links
+            .into_iter()
+            .find(|(range, _)| range.contains(&offset))
+            .map(|(_, url)| url.clone())
+    }
+
+    /// Follow a link from the document: jump to an anchor, open a local
+    /// document, or hand anything else to the system's URL opener
+    pub fn follow_link(&mut self, url: &str) {
+        if let Some(anchor) = url.strip_prefix('#') {
+            self.jump_to_anchor(anchor);
+            return;
+        }
+        if is_external(url) {
+            self.status_message = Some(match open_url(url) {
+                Ok(()) => format!("Opened {}", url),
+                Err(e) => format!("Could not open {}: {}", url, e),
+            });
+            return;
+        }
+
+        let (target, anchor) = url.split_once('#').unwrap_or((url, ""));
+        let target = percent_decode(target.split('?').next().unwrap_or_default());
+        let base = match self.current_file {
+            Some(ref file) => file.parent().unwrap_or(&self.root_path).to_path_buf(),
+            None => self.root_path.clone(),
+        };
+        let path = match target.strip_prefix('/') {
+            Some(rooted) => self.root_path.join(rooted),
+            None => base.join(&target),
+        };
+        let path = fileops::normalize(&path);
+
+        if path.is_dir() {
+            self.select_path(&path);
+            self.focus = Focus::Sidebar;
+        } else if path.is_file() {
+            self.open_path(&path.to_string_lossy());
+            if !anchor.is_empty() {
+                self.jump_to_anchor(anchor);
+            }
+        } else {
+            self.status_message = Some(format!("Link target not found: {}", target));
+        }
+    }
+
+    /// Scroll to the heading whose slug is `anchor`
+    fn jump_to_anchor(&mut self, anchor: &str) {
+        let anchor = percent_decode(anchor).to_lowercase();
+        let mut slugs: Vec<String> = Vec::new();
+        for idx in 0..self.rendered_content.len() {
+            let line = &self.rendered_content[idx];
+            if line.heading_level == 0 {
+                continue;
+            }
+            // Numbered like GitHub does for repeated headings
+            let text = line.to_plain_string();
+            let base = heading_slug(text.trim_start_matches('#'));
+            let mut slug = base.clone();
+            let mut n = 1;
+            while slugs.contains(&slug) {
+                slug = format!("{}-{}", base, n);
+                n += 1;
+            }
+            if slug == anchor {
+                let max_scroll = self.total_wrapped_lines().saturating_sub(self.content_height as usize);
+                self.content_scroll = self.source_to_wrapped_index(idx).min(max_scroll);
+                return;
+            }
+            slugs.push(slug);
+        }
+        self.status_message = Some(format!("No heading for #{}", anchor));
+    }
+
+    /// Scroll so the thumb sits under `row` of the scrollbar
+    fn scroll_to_scrollbar_row(&mut self, row: u16) {
+        let Some(bar) = self.areas.scrollbar else { return };
+        let max_scroll = self.total_wrapped_lines().saturating_sub(self.content_height as usize);
+        let span = bar.height.saturating_sub(1).max(1) as usize;
+        let pos = (row.saturating_sub(bar.y) as usize).min(span);
+        self.content_scroll = (pos * max_scroll + span / 2) / span;
+    }
+
+    pub fn set_content_height(&mut self, height: u16) {
+        self.content_height = height.saturating_sub(2); // Account for borders
+    }
+
+    pub fn enter_search_mode(&mut self) {
+        self.history_pos = None;
+        self.mode = AppMode::Search;
+        self.search_index.mark_stale();
+        self.search_query.clear();
+        self.clear_search_results();
+        self.search_error = None;
+        self.searcher = None;
+    }
+
+    pub fn exit_search_mode(&mut self) {
+        self.search_history.record(true, &self.search_query);
+        self.mode = AppMode::Normal;
+        self.search_query.clear();
+        self.clear_search_results();
+        self.search_error = None;
+        self.searcher = None;
+    }
+
+    /// Switch both searches between literal text and regular expressions
+    pub fn toggle_search_regex(&mut self) {
+        self.settings.search.regex = !self.settings.search.regex;
+        self.settings.search.fuzzy = false;
+        self.search_options_changed();
+    }
+
+    /// Switch global search between literal text and fuzzy matching
+    pub fn toggle_search_fuzzy(&mut self) {
+        self.settings.search.fuzzy = !self.settings.search.fuzzy;
+        self.settings.search.regex = false;
+        self.search_options_changed();
+    }
+
+    /// Cycle through ignoring case, smart case and matching case
+    pub fn cycle_search_case(&mut self) {
+        self.settings.search.case = self.settings.search.case.next();
+        self.search_options_changed();
+    }
+
+    pub fn toggle_search_whole_word(&mut self) {
+        self.settings.search.whole_word = !self.settings.search.whole_word;
+        self.search_options_changed();
+    }
+
+    /// Save the search toggles and re-run the search being typed
+    fn search_options_changed(&mut self) {
+        self.settings.save();
+        match self.mode {
+            AppMode::Search => self.perform_search(),
+            AppMode::DocumentSearch => self.perform_doc_search(),
+            _ => {}
+        }
+    }
+
+    pub fn enter_settings_mode(&mut self) {
+        self.mode = AppMode::Settings;
+        self.settings_selected = 0;
+    }
+
+    pub fn exit_settings_mode(&mut self) {
+        self.mode = AppMode::Normal;
+    }
+
+    pub fn settings_toggle_current(&mut self) {
+        match self.settings_selected {
+            0 => self.settings.show_line_numbers = !self.settings.show_line_numbers,
+            1 => {
+                self.settings.theme = match self.settings.theme {
+                    Theme::Dark => Theme::Light,
+                    Theme::Light => Theme::Dark,
+                };
+            }
+            2 => {
+                self.settings.wrap_width = self.settings.wrap_width.next();
+            }
+            3 => {
+                self.settings.sort_mode = self.settings.sort_mode.next();
+                self.refresh_tree(&FsChanges::default());
+            }
+            4 => {
+                self.settings.show_modified_column = !self.settings.show_modified_column;
+            }
+            5 => {
+                self.settings.show_word_count_column = !self.settings.show_word_count_column;
+                self.refresh_tree(&FsChanges::default());
+            }
+            6 => {
+                self.settings.sidebar_label = self.settings.sidebar_label.next();
+                self.apply_sidebar_label();
+            }
+            _ => {}
+        }
+        self.settings.save();
+    }
+
+    /// Label sidebar documents with their titles, or clear the titles again,
+    /// below `path` (which must already be loaded)
+    fn fill_titles(&mut self, path: &Path) {
+        let show_titles = self.settings.sidebar_label == SidebarLabel::Title;
+        let options = self.settings.tree_options(self.show_ignored);
+        if let Some(node) = self.tree.find_mut(path) {
+            set_titles(node, show_titles, &options, &mut self.titles);
+        }
+    }
+
+    /// Switch the sidebar between file names and document titles
+    pub fn toggle_sidebar_label(&mut self) {
+        self.settings.sidebar_label = self.settings.sidebar_label.next();
+        self.settings.save();
+        self.apply_sidebar_label();
+        self.status_message = Some(format!("Sidebar: {}", self.settings.sidebar_label.display_name()));
+    }
+
+    fn apply_sidebar_label(&mut self) {
+        let selected_path = self.selected_path();
+        let root = self.root_path.clone();
+        self.fill_titles(&root);
+        if !self.sidebar_filter.is_empty() {
+            self.update_filter();
+        } else if let Some(path) = selected_path {
+            self.selected_index = self.tree.visible_index_of(&path).unwrap_or(self.selected_index);
+        }
+    }
+
+    /// Switch to the next sidebar sort mode
+    pub fn cycle_sort_mode(&mut self) {
+        self.settings.sort_mode = self.settings.sort_mode.next();
+        self.settings.save();
+        self.refresh_tree(&FsChanges::default());
+        self.status_message = Some(format!("Sort: {}", self.settings.sort_mode.display_name()));
+    }
+
+    pub fn settings_next(&mut self) {
+        if self.settings_selected < LAST_SETTING {
+            self.settings_selected += 1;
+        }
+    }
+
+    pub fn settings_previous(&mut self) {
+        if self.settings_selected > 0 {
+            self.settings_selected -= 1;
+        }
+    }
+
+    pub fn enter_doc_search_mode(&mut self) {
+        self.mode = AppMode::DocumentSearch;
+        self.history_pos = None;
+        self.clear_doc_search();
+    }
+
+    /// Close the prompt, keeping the matches highlighted for `n` / `N`
+    pub fn exit_doc_search_mode(&mut self) {
+        self.mode = AppMode::Normal;
+        self.search_history.record(false, &self.doc_search_query);
+        if self.search_error.is_some() {
+            self.clear_doc_search();
+        }
+    }
+
+    /// Whether a document search is still active after its prompt closed
+    pub fn has_doc_search(&self) -> bool {
+        self.doc_search_pattern.is_some()
+    }
+
+    pub fn clear_doc_search(&mut self) {
+        self.doc_search_query.clear();
+        self.doc_search_matches.clear();
+        self.doc_search_current = 0;
+        self.doc_search_pattern = None;
+        self.search_error = None;
+    }
+
+    pub fn doc_search_add_char(&mut self, c: char) {
+        self.doc_search_query.push(c);
+        self.history_pos = None;
+        self.perform_doc_search();
+    }
+
+    pub fn doc_search_backspace(&mut self) {
+        self.doc_search_query.pop();
+        self.history_pos = None;
+        self.perform_doc_search();
+    }
+
+    /// Show the previous (older) query from the open prompt's history
+    pub fn history_previous(&mut self) {
+        let len = self.history_queries().len();
+        let pos = match self.history_pos {
+            Some(0) => return,
+            Some(pos) => pos - 1,
+            None if len == 0 => return,
+            None => {
+                self.history_draft = self.prompt_query().to_string();
+                len - 1
+            }
+        };
+        let query = self.history_queries()[pos].clone();
+        self.history_pos = Some(pos);
+        self.set_prompt_query(query);
+    }
+
+    /// Show the next (newer) query from the history, and after the newest,
+    /// what was typed before
+    pub fn history_next(&mut self) {
+        let Some(pos) = self.history_pos else {
+            return;
+        };
+        let query = match self.history_queries().get(pos + 1).cloned() {
+            Some(query) => {
+                self.history_pos = Some(pos + 1);
+                query
+            }
+            None => {
+                self.history_pos = None;
+                std::mem::take(&mut self.history_draft)
+            }
+        };
+        self.set_prompt_query(query);
+    }
+
+    fn history_queries(&self) -> &[String] {
+        match self.mode {
+            AppMode::Search => &self.search_history.global,
+            _ => &self.search_history.document,
+        }
+    }
+
+    fn prompt_query(&self) -> &str {
+        match self.mode {
+            AppMode::Search => &self.search_query,
+            _ => &self.doc_search_query,
+        }
+    }
+
+    fn set_prompt_query(&mut self, query: String) {
+        match self.mode {
+            AppMode::Search => {
+                self.search_query = query;
+                self.perform_search();
+            }
+            AppMode::DocumentSearch => {
+                self.doc_search_query = query;
+                self.perform_doc_search();
+            }
+            _ => {}
+        }
+    }
+
+    pub fn doc_search_next(&mut self) {
+        if !self.doc_search_matches.is_empty() {
+            self.doc_search_current = (self.doc_search_current + 1) % self.doc_search_matches.len();
+            self.jump_to_current_match();
+        }
+    }
+
+    pub fn doc_search_previous(&mut self) {
+        if !self.doc_search_matches.is_empty() {
+            if self.doc_search_current == 0 {
+                self.doc_search_current = self.doc_search_matches.len() - 1;
+            } else {
+                self.doc_search_current -= 1;
+            }
+            self.jump_to_current_match();
+        }
+    }
+
+    fn perform_doc_search(&mut self) {
+        // An unfinished regex keeps the last pattern that compiled, with the
+        // error shown, matched again in case the document changed
+        let pattern = match search::compile(&self.doc_search_query, &self.settings.search) {
+            Ok(pattern) => pattern,
+            Err(message) => {
+                self.search_error = Some(message);
+                if let Some(pattern) = self.doc_search_pattern.clone() {
+                    self.doc_search_matches = self.find_doc_matches(&pattern);
+                    self.doc_search_current = self.doc_search_current.min(self.doc_search_matches.len().saturating_sub(1));
+                }
+                return;
+            }
+        };
+        self.search_error = None;
+        self.doc_search_matches.clear();
+        self.doc_search_current = 0;
+        self.doc_search_pattern = None;
+
+        if self.doc_search_query.is_empty() {
+            return;
+        }
+
+        self.doc_search_matches = self.find_doc_matches(&pattern);
+        self.doc_search_pattern = Some(pattern);
+
+        // Jump to first match if found
+        if !self.doc_search_matches.is_empty() {
+            self.jump_to_current_match();
+        }
+    }
+
+    /// Every occurrence of `pattern`, in the text as it is drawn so matches and
+    /// highlights agree
+    fn find_doc_matches(&self, pattern: &Regex) -> Vec<DocMatch> {
+        let mut matches = Vec::new();
+        for (idx, line) in self.rendered_content.iter().enumerate() {
+            let text = line.to_plain_string();
+            for range in search::find_ranges(pattern, &text) {
+                matches.push(DocMatch { line: idx, range });
+            }
+        }
+        matches
+    }
+
+    fn jump_to_current_match(&mut self) {
+        let current = self.doc_search_matches.get(self.doc_search_current);
+        if let Some((m, line)) = current.and_then(|m| Some((m, self.rendered_content.get(m.line)?))) {
+            // The row of a wrapped line the match starts on
+            let text = line.to_plain_string();
+            let row = wrapped_rows(line, &text, self.settings.wrap_width.to_usize())
+                .and_then(|rows| rows.iter().position(|words| words.last().is_some_and(|w| m.range.start < w.end)))
+                .unwrap_or(0);
+            let wrapped_idx = self.source_to_wrapped_index(m.line) + row;
+            // Scroll to show the match, centered if possible
+            let half_height = (self.content_height / 2) as usize;
+            self.content_scroll = wrapped_idx.saturating_sub(half_height);
+        }
+    }
+
+    /// Convert a source line index to its wrapped line index
+    fn source_to_wrapped_index(&self, source_idx: usize) -> usize {
+        let max_width = self.settings.wrap_width.to_usize();
+        let mut wrapped_idx = 0;
+
+        for (idx, line) in self.rendered_content.iter().enumerate() {
+            if idx == source_idx {
+                return wrapped_idx;
+            }
+            // Count how many wrapped lines this source line produces
+            wrapped_idx += self.count_wrapped_lines(line, max_width);
+        }
+
+        wrapped_idx
+    }
+
+    /// Count how many wrapped lines a single RenderedLine produces
+    fn count_wrapped_lines(&self, line: &RenderedLine, max_width: Option<usize>) -> usize {
+        if line.segments.is_empty() {
+            return 1;
+        }
+
+        // Don't wrap table rows or separators
+        if line.is_table_row || line.is_table_separator {
+            return 1;
+        }
+
+        // If no wrapping, return 1
+        let max_width = match max_width {
+            Some(w) => w,
+            None => return 1,
+        };
+
+        // Get the full text content to check length
+        let full_text: String = line.segments.iter().map(|seg| {
+            match seg {
+                TextSegment::Plain(s) => s.clone(),
+                TextSegment::Code(s) => format!("`{}`", s),
+                TextSegment::Link { text, .. } => text.clone(),
+                TextSegment::Emphasis(s) => s.clone(),
+                TextSegment::Strong(s) => s.clone(),
+            }
+        }).collect();
+
+        // If line fits, no wrapping
+        if full_text.len() <= max_width {
+            return 1;
+        }
+
+        // Count wrapped lines
+        let mut count = 0;
+        let mut current_len = 0;
+
+        for word in full_text.split_whitespace() {
+            if current_len == 0 {
+                current_len = word.len();
+            } else if current_len + 1 + word.len() <= max_width {
+                current_len += 1 + word.len();
+            } else {
+                count += 1;
+                current_len = word.len();
+            }
+        }
+
+        if current_len > 0 {
+            count += 1;
+        }
+
+        if count == 0 {
+            1
+        } else {
+            count
+        }
+    }
+
+    /// Convert a wrapped line index back to the source line it belongs to
+    fn wrapped_to_source_index(&self, wrapped_idx: usize) -> usize {
+        let max_width = self.settings.wrap_width.to_usize();
+        let mut total = 0;
+
+        for (idx, line) in self.rendered_content.iter().enumerate() {
+            total += self.count_wrapped_lines(line, max_width);
+            if total > wrapped_idx {
+                return idx;
+            }
+        }
+
+        self.rendered_content.len().saturating_sub(1)
+    }
+
+    /// Get total number of wrapped lines
+    pub fn total_wrapped_lines(&self) -> usize {
         let max_width = self.settings.wrap_width.to_usize();
         self.rendered_content.iter()
             .map(|line| self.count_wrapped_lines(line, max_width))
@@ -621,16 +2509,38 @@ impl App {
 
     pub fn search_add_char(&mut self, c: char) {
         self.search_query.push(c);
+        self.history_pos = None;
         self.perform_search();
     }
 
     pub fn search_backspace(&mut self) {
         self.search_query.pop();
+        self.history_pos = None;
         self.perform_search();
     }
 
+    /// Up in the global search prompt: the previous result, or from the
+    /// first one or while going through the history, the previous query in it
+    pub fn search_up(&mut self) {
+        if self.search_selected == 0 || self.history_pos.is_some() {
+            self.history_previous();
+        } else {
+            self.search_previous();
+        }
+    }
+
+    /// Down in the global search prompt: the next result, or with none
+    /// listed or while going through the history, the next query in it
+    pub fn search_down(&mut self) {
+        if self.search_rows == 0 || self.history_pos.is_some() {
+            self.history_next();
+        } else {
+            self.search_next();
+        }
+    }
+
     pub fn search_next(&mut self) {
-        if !self.search_results.is_empty() && self.search_selected < self.search_results.len() - 1 {
+        if self.search_selected + 1 < self.search_rows {
             self.search_selected += 1;
         }
     }
@@ -641,102 +2551,379 @@ impl App {
         }
     }
 
-    pub fn search_select(&mut self) {
-        if let Some(result) = self.search_results.get(self.search_selected) {
-            let path = result.path.clone();
-            let query = self.search_query.clone();
-            self.load_file(&path);
-            self.exit_search_mode();
-            self.focus = Focus::Content;
+    /// What is listed on a row of the overlay: each result document,
+    /// followed by its hits
+    pub fn search_row(&self, row: usize) -> Option<SearchRow> {
+        if row >= self.search_rows {
+            return None;
+        }
+        let file = self.search_row_starts.partition_point(|&start| start <= row) - 1;
+        Some(match row - self.search_row_starts[file] {
+            0 => SearchRow::File(file),
+            hit => SearchRow::Hit(file, hit - 1),
+        })
+    }
+
+    fn clear_search_results(&mut self) {
+        self.search_results.clear();
+        self.search_row_starts.clear();
+        self.search_rows = 0;
+        self.search_selected = 0;
+        self.search_scroll = 0;
+    }
+
+    /// List `result` at position `idx`, keeping the same row selected and
+    /// the rows in view in place if it goes above them
+    fn insert_search_result(&mut self, idx: usize, result: SearchResult) {
+        let rows = 1 + result.hits.len();
+        let start = self.search_row_starts.get(idx).copied().unwrap_or(self.search_rows);
+        for later in &mut self.search_row_starts[idx..] {
+            *later += rows;
+        }
+        self.search_row_starts.insert(idx, start);
+        self.search_results.insert(idx, result);
+
+        if self.search_rows > 0 && start <= self.search_selected {
+            self.search_selected += rows;
+        }
+        if start < self.search_scroll {
+            self.search_scroll += rows;
+        }
+        self.search_rows += rows;
+    }
+
+    /// Scroll the result list so the selection is visible, and have a
+    /// waiting search read on once the list is scrolled close to its end
+    pub fn fit_search_scroll(&mut self, height: usize) {
+        if self.search_selected < self.search_scroll {
+            self.search_scroll = self.search_selected;
+        } else if height > 0 && self.search_selected >= self.search_scroll + height {
+            self.search_scroll = self.search_selected + 1 - height;
+        }
+
+        if let Some(ref searcher) = self.searcher {
+            if self.search_waiting && self.search_scroll + 2 * height >= self.search_rows {
+                self.search_waiting = false;
+                searcher.read_more();
+            }
+        }
+    }
 
-            // Automatically open document search with the same query
+    /// Open the selected document, at the selected hit if it is one
+    pub fn search_select(&mut self) {
+        let (file, hit) = match self.search_row(self.search_selected) {
+            Some(SearchRow::File(file)) => (file, None),
+            Some(SearchRow::Hit(file, hit)) => (file, Some(hit)),
+            None => return,
+        };
+        let result = &self.search_results[file];
+        let path = result.path.clone();
+        let selected = hit.map(|hit| (result.hits[hit].line, result.hits[hit].occurrence));
+        // Every hit's line, occurrence and matched text, to tell which
+        // document search match the selected one is
+        let hits: Vec<(usize, usize, String)> = result
+            .hits
+            .iter()
+            .map(|h| (h.line, h.occurrence, h.highlights.first().map_or("", |r| &h.context[r.clone()]).to_string()))
+            .collect();
+        let query = self.doc_query_for(file, hit);
+        self.load_file(&path);
+        self.exit_search_mode();
+        self.focus = Focus::Content;
+
+        // Automatically open document search with the same query
+        if !query.is_empty() {
             self.doc_search_query = query;
             self.perform_doc_search();
             self.mode = AppMode::DocumentSearch;
         }
+        if let Some((line, occurrence)) = selected {
+            self.jump_to_hit(line, occurrence, &hits);
+        }
     }
 
-    fn perform_search(&mut self) {
-        self.search_results.clear();
-        self.search_selected = 0;
-
-        if self.search_query.is_empty() {
+    /// Make the document search match a global search hit corresponds to
+    /// current, given the line, occurrence and matched text of the
+    /// document's hits, or else scroll to its line
+    fn jump_to_hit(&mut self, line: usize, occurrence: usize, hits: &[(usize, usize, String)]) {
+        let Some(rendered) = self.rendered_line_of(line) else {
             return;
+        };
+        // The matches before it on the rendered line: those document search
+        // also finds on the source lines joined before it, and earlier on its own
+        let first_line = self.rendered_content[rendered].source_line;
+        let earlier = match self.doc_search_pattern {
+            Some(ref pattern) => hits
+                .iter()
+                .filter(|(l, o, _)| (first_line..line).contains(l) || (*l == line && *o < occurrence))
+                .filter(|(_, _, text)| search::is_match(pattern, text))
+                .count(),
+            None => 0,
+        };
+        self.jump_to_rendered_line(rendered, earlier);
+    }
+
+    /// What document search looks for after opening a result: the regex,
+    /// or the word or phrase that matched, without any filters. Nothing
+    /// for a fuzzy query, which document search can't match.
+    fn doc_query_for(&self, file: usize, hit: Option<usize>) -> String {
+        let options = &self.settings.search;
+        if options.regex {
+            return search::Query::parse_regex(&self.search_query).1;
+        } else if options.fuzzy {
+            return String::new();
+        }
+        let parts = search::Query::parse(&self.search_query).parts;
+        let Some(hit) = hit.map(|hit| &self.search_results[file].hits[hit]) else {
+            return parts.into_iter().next().unwrap_or_default();
+        };
+        let Some(highlight) = hit.highlights.first() else {
+            return parts.into_iter().next().unwrap_or_default();
+        };
+        let matched = &hit.context[highlight.clone()];
+        parts
+            .into_iter()
+            .find(|part| search::compile(part, options).is_ok_and(|pattern| search::is_match(&pattern, matched)))
+            .unwrap_or_else(|| matched.to_string())
+    }
+
+    /// The rendered line a source line ended up in. Paragraphs are joined
+    /// into one rendered line, starting at their first source line.
+    fn rendered_line_of(&self, line: usize) -> Option<usize> {
+        self.rendered_content
+            .iter()
+            .rposition(|l| l.source_line <= line && !l.segments.is_empty())
+    }
+
+    /// Make the document search match after `earlier` others on a rendered
+    /// line current, or else scroll to the line
+    fn jump_to_rendered_line(&mut self, rendered: usize, earlier: usize) {
+        match self.doc_search_matches.iter().position(|m| m.line == rendered) {
+            Some(first) => {
+                let nth = first + earlier;
+                let on_line = self.doc_search_matches.get(nth).is_some_and(|m| m.line == rendered);
+                self.doc_search_current = if on_line { nth } else { first };
+                self.jump_to_current_match();
+            }
+            None => {
+                let half_height = (self.content_height / 2) as usize;
+                self.content_scroll = self.source_to_wrapped_index(rendered).saturating_sub(half_height);
+            }
         }
+    }
 
-        let query_lower = self.search_query.to_lowercase();
+    fn perform_search(&mut self) {
+        let files: Vec<_> = self
+            .all_documents()
+            .iter()
+            .map(|path| (path.clone(), self.kind_of(path)))
+            .collect();
+        let total = files.len();
+        let job = SearchJob::new(
+            &self.search_query,
+            &self.settings.search,
+            files,
+            &self.root_path,
+            &self.search_index,
+        );
+        let job = match job {
+            Ok(job) => job,
+            Err(message) => {
+                self.search_error = Some(message);
+                return;
+            }
+        };
+        self.search_error = None;
+        self.clear_search_results();
+        self.search_scanned = 0;
+        self.search_total = total;
+        self.search_indexing = None;
+        self.search_waiting = false;
+        self.search_title_matches = 0;
+        self.searcher = None; // Cancels a search for an older query
+
+        if self.search_query.trim().is_empty() {
+            return;
+        }
+        self.searcher = Some(Searcher::start(job));
+    }
 
-        // Collect all markdown files from the tree
-        let files = self.collect_all_files(&self.tree.clone());
+    /// Whether a global search is still running, and not just waiting to
+    /// be scrolled further
+    pub fn is_searching(&self) -> bool {
+        self.searcher.is_some() && !self.search_waiting
+    }
 
-        for file_path in files {
-            if let Ok(content) = fs::read_to_string(&file_path) {
-                // Find first matching line as preview
-                let mut match_preview = String::new();
-                let mut match_count = 0;
+    /// Add results from the background search. Returns true if anything changed.
+    pub fn process_search_events(&mut self) -> bool {
+        let Some(ref searcher) = self.searcher else {
+            return false;
+        };
+        let events = searcher.poll_events();
+        if events.is_empty() {
+            return false;
+        }
 
-                for line in content.lines() {
-                    if line.to_lowercase().contains(&query_lower) {
-                        if match_preview.is_empty() {
-                            match_preview = line.trim().chars().take(60).collect();
+        let order = searcher.order;
+        for event in events {
+            match event {
+                SearchEvent::Indexing(done, total) => self.search_indexing = Some((done, total)),
+                SearchEvent::Candidates(total) => {
+                    self.search_indexing = None;
+                    self.search_total = total;
+                }
+                SearchEvent::Result(result) => {
+                    self.titles.insert(&result.path, result.title.clone());
+                    let idx = match order {
+                        ResultOrder::Ranked => self.search_results.len(),
+                        // Documents whose title matches come first
+                        ResultOrder::TitleMatchesFirst if result.title_match => {
+                            self.search_title_matches += 1;
+                            self.search_title_matches - 1
                         }
-                        match_count += 1;
-                    }
+                        ResultOrder::TitleMatchesFirst => self.search_results.len(),
+                        // Highest score first, ties in tree order
+                        ResultOrder::Score => self.search_results.partition_point(|r| r.score >= result.score),
+                    };
+                    self.insert_search_result(idx, result);
                 }
+                SearchEvent::Waiting => self.search_waiting = true,
+                SearchEvent::Scanned => self.search_scanned += 1,
+                SearchEvent::Finished => self.searcher = None,
+            }
+        }
+        true
+    }
+}
 
-                if match_count > 0 {
-                    let name = file_path
-                        .file_name()
-                        .map(|s| s.to_string_lossy().to_string())
-                        .unwrap_or_default();
+// Helper functions
 
-                    let preview = if match_count > 1 {
-                        format!("{} ({} matches)", match_preview, match_count)
-                    } else {
-                        match_preview
-                    };
+/// Byte offset in `text` of the character at `column` of wrapped `row`,
+/// wrapping words the same way the content pane does
+fn wrapped_offset(text: &str, max_width: usize, row: usize, column: usize) -> Option<usize> {
+    let mut col = 0;
+    for word in word_rows(text, max_width).get(row)? {
+        let chars = text[word.clone()].chars().count();
+        if column < col + chars {
+            return text[word.clone()].char_indices().nth(column - col).map(|(idx, _)| word.start + idx);
+        }
+        col += chars + 1;
+    }
+    None
+}
 
-                    self.search_results.push(SearchResult {
-                        path: file_path.clone(),
-                        name,
-                        match_preview: preview,
-                    });
+/// The words on each row a rendered line's `text` wraps to, as byte ranges,
+/// or `None` if the line is drawn as it is on a single row
+pub fn wrapped_rows(line: &RenderedLine, text: &str, max_width: Option<usize>) -> Option<Vec<Vec<Range<usize>>>> {
+    let max_width = max_width?;
+    if line.is_table_row || line.is_table_separator || text.len() <= max_width {
+        return None;
+    }
+    Some(word_rows(text, max_width))
+}
 
-                    // Limit results
-                    if self.search_results.len() >= 50 {
-                        return;
-                    }
-                }
+/// Word-wrap `text` the way the content pane does: rows of whitespace-separated
+/// words (as byte ranges), drawn joined by single spaces
+fn word_rows(text: &str, max_width: usize) -> Vec<Vec<Range<usize>>> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (idx, c) in text.char_indices() {
+        if c.is_whitespace() {
+            if let Some(s) = start.take() {
+                words.push(s..idx);
             }
+        } else if start.is_none() {
+            start = Some(idx);
         }
     }
+    if let Some(s) = start {
+        words.push(s..text.len());
+    }
 
-    fn collect_all_files(&self, node: &TreeNode) -> Vec<PathBuf> {
-        let mut files = Vec::new();
-
-        if !node.is_dir {
-            files.push(node.path.clone());
+    let mut rows: Vec<Vec<Range<usize>>> = vec![Vec::new()];
+    let mut current_len = 0;
+    for word in words {
+        if current_len == 0 {
+            current_len = word.len();
+        } else if current_len + 1 + word.len() <= max_width {
+            current_len += 1 + word.len();
+        } else {
+            rows.push(Vec::new());
+            current_len = word.len();
         }
-
-        for child in &node.children {
-            files.extend(self.collect_all_files(child));
+        if let Some(row) = rows.last_mut() {
+            row.push(word);
         }
+    }
+    rows
+}
+
+/// Open a URL with the desktop's default handler, without waiting for it
+fn open_url(url: &str) -> std::io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    let mut child = command
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    thread::spawn(move || child.wait());
+    Ok(())
+}
 
-        files
+/// Fill in (or clear) the titles of the documents below `node`, then re-sort,
+/// since titles take the place of names when sorting
+fn set_titles(node: &mut TreeNode, show_titles: bool, options: &TreeOptions, titles: &mut TitleCache) {
+    for child in &mut node.children {
+        if child.is_dir {
+            set_titles(child, show_titles, options, titles);
+        } else if show_titles && options.file_types.kind_of(&child.path) != Some(DocKind::PlainText) {
+            child.title = titles.get(&child.path);
+        } else {
+            child.title = None;
+        }
     }
+    sort_children(node, options.sort_mode);
+}
+fn common_prefix(candidates: &[String]) -> String {
+    let Some(first) = candidates.first() else {
+        return String::new();
+    };
+    let mut prefix_len = first.len();
+    for candidate in &candidates[1..] {
+        prefix_len = first
+            .char_indices()
+            .zip(candidate.chars())
+            .take_while(|((_, a), b)| a == b)
+            .map(|((i, a), _)| i + a.len_utf8())
+            .last()
+            .unwrap_or(0)
+            .min(prefix_len);
+    }
+    first[..prefix_len].to_string()
 }
 
-// Helper functions
 fn create_plain_line(text: &str) -> RenderedLine {
     let mut line = RenderedLine {
         segments: Vec::new(),
         heading_level: 0,
         is_code_block: false,
+        is_code_fence: false,
+        code_language: None,
         is_blockquote: false,
         is_list_item: false,
         is_horizontal_rule: false,
         is_table_row: false,
         is_table_separator: false,
+        source_line: 0,
     };
     line.segments.push(TextSegment::Plain(text.to_string()));
     line
@@ -747,11 +2934,14 @@ fn create_heading_line(text: &str, level: u8) -> RenderedLine {
         segments: Vec::new(),
         heading_level: level,
         is_code_block: false,
+        is_code_fence: false,
+        code_language: None,
         is_blockquote: false,
         is_list_item: false,
         is_horizontal_rule: false,
         is_table_row: false,
         is_table_separator: false,
+        source_line: 0,
     };
     line.segments.push(TextSegment::Plain(text.to_string()));
     line
@@ -763,11 +2953,81 @@ impl RenderedLine {
             segments: Vec::new(),
             heading_level: 0,
             is_code_block: false,
+            is_code_fence: false,
+            code_language: None,
             is_blockquote: false,
             is_list_item: false,
             is_horizontal_rule: false,
             is_table_row: false,
             is_table_separator: false,
+            source_line: 0,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app_with_doc(name: &str, content: &str) -> App {
+        let root = std::env::temp_dir().join(format!("rmd-test-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&root).unwrap();
+        let path = root.join("doc.md");
+        fs::write(&path, content).unwrap();
+        let settings = Settings::default();
+        let tree = load_root(&root, &settings.tree_options(false));
+        let mut app = App::with_tree(root, settings, tree);
+        app.load_file(&path);
+        app
+    }
+
+    fn hits(hits: &[(usize, usize)]) -> Vec<(usize, usize, String)> {
+        hits.iter().map(|&(line, occurrence)| (line, occurrence, "foo".to_string())).collect()
+    }
+
+    #[test]
+    fn go_to_line_counts_source_lines() {
+        let content = "---\ntitle: Doc\n---\n# Title\n\nA paragraph\nover two lines\n\n\n## Next\n\nText\n";
+        let mut app = app_with_doc("go-to-line", content);
+        app.focus = Focus::Content;
+        app.content_height = 1;
+        let top_source_line = |app: &App| app.rendered_content[app.wrapped_to_source_index(app.content_scroll)].source_line;
+
+        app.go_to_line(10);
+        assert_eq!(top_source_line(&app), 9);
+        // A line inside a paragraph goes to the paragraph
+        app.go_to_line(7);
+        assert_eq!(top_source_line(&app), 5);
+
+        let _ = fs::remove_dir_all(&app.root_path);
+    }
+
+    #[test]
+    fn hit_selects_its_own_occurrence_on_a_line() {
+        let mut app = app_with_doc("hit-occurrence", "# Title\n\nfoo and then foo again\n");
+        app.doc_search_query = "foo".to_string();
+        app.perform_doc_search();
+        assert_eq!(app.doc_search_matches.len(), 2);
+
+        let hits = hits(&[(2, 0), (2, 1)]);
+        app.jump_to_hit(2, 1, &hits);
+        assert_eq!(app.doc_search_current, 1);
+        app.jump_to_hit(2, 0, &hits);
+        assert_eq!(app.doc_search_current, 0);
+
+        let _ = fs::remove_dir_all(&app.root_path);
+    }
+
+    #[test]
+    fn hit_counts_matches_on_the_lines_joined_before_it() {
+        let mut app = app_with_doc("hit-joined", "# Title\n\nfoo on one line\nand foo, foo on the next\n");
+        app.doc_search_query = "foo".to_string();
+        app.perform_doc_search();
+        assert_eq!(app.doc_search_matches.len(), 3);
+
+        app.jump_to_hit(3, 1, &hits(&[(2, 0), (3, 0), (3, 1)]));
+        assert_eq!(app.doc_search_current, 2);
+
+        let _ = fs::remove_dir_all(&app.root_path);
+    }
+}
@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use pulldown_cmark::{Event, LinkType, Options, Parser, Tag};
+
+use crate::file_tree::{build_tree, TreeNode, TreeOptions};
+use crate::filetype::{strip_jsx, DocKind, FileTypes};
+use crate::markdown::heading_slugs;
+
+/// Options for the `check` subcommand
+#[derive(Debug, Clone, Default)]
+pub struct CheckOptions {
+    /// Also request external http(s) URLs
+    pub external: bool,
+    /// Recognised document types
+    pub file_types: FileTypes,
+}
+
+/// A single broken link found in a document
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub path: PathBuf,
+    pub line: usize,
+    pub message: String,
+}
+
+/// Check every Markdown file under `root` for broken links, anchors and images
+pub fn check_links(root: &Path, options: &CheckOptions) -> Vec<Diagnostic> {
+    let tree_options = TreeOptions {
+        file_types: options.file_types.clone(),
+        ..TreeOptions::default()
+    };
+    let tree = build_tree(root, &tree_options);
+    let mut files = Vec::new();
+    collect_files(&tree, &mut files);
+
+    let mut checker = Checker {
+        root: root.to_path_buf(),
+        options: options.clone(),
+        slug_cache: HashMap::new(),
+        url_cache: HashMap::new(),
+        diagnostics: Vec::new(),
+    };
+
+    for file in files {
+        checker.check_file(&file);
+    }
+
+    checker.diagnostics
+}
+
+fn collect_files(node: &TreeNode, files: &mut Vec<PathBuf>) {
+    if !node.is_dir {
+        files.push(node.path.clone());
+    }
+    for child in &node.children {
+        collect_files(child, files);
+    }
+}
+
+struct Checker {
+    root: PathBuf,
+    options: CheckOptions,
+    slug_cache: HashMap<PathBuf, Vec<String>>,
+    url_cache: HashMap<String, Option<String>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker {
+    fn check_file(&mut self, path: &Path) {
+        // Plain text files have no link syntax
+        if self.options.file_types.kind_of(path) == Some(DocKind::PlainText) {
+            return;
+        }
+
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) => {
+                self.report(path, 1, format!("cannot read file: {}", e));
+                return;
+            }
+        };
+
+        let line_starts: Vec<usize> = std::iter::once(0)
+            .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        let line_of = |offset: usize| match line_starts.binary_search(&offset) {
+            Ok(idx) => idx + 1,
+            Err(idx) => idx,
+        };
+
+        let mut targets = Vec::new();
+        let parser = Parser::new_ext(&content, Options::ENABLE_TABLES).into_offset_iter();
+        for (event, range) in parser {
+            match event {
+                // `<me@example.com>` has no scheme, but is not a file either
+                Event::Start(Tag::Link { link_type: LinkType::Email, .. }) => {}
+                Event::Start(Tag::Link { dest_url, .. }) => {
+                    targets.push((line_of(range.start), dest_url.to_string(), false));
+                }
+                Event::Start(Tag::Image { dest_url, .. }) => {
+                    targets.push((line_of(range.start), dest_url.to_string(), true));
+                }
+                _ => {}
+            }
+        }
+
+        for (line, url, is_image) in targets {
+            if let Some(message) = self.check_target(path, &content, &url, is_image) {
+                self.report(path, line, message);
+            }
+        }
+    }
+
+    /// Check a single link target, returning a message if it is broken
+    fn check_target(&mut self, source: &Path, content: &str, url: &str, is_image: bool) -> Option<String> {
+        if url.is_empty() {
+            return Some("empty link target".to_string());
+        }
+
+        if is_external(url) {
+            if self.options.external && (url.starts_with("http://") || url.starts_with("https://")) {
+                return self.check_url(url);
+            }
+            return None;
+        }
+
+        let (path_part, fragment) = match url.split_once('#') {
+            Some((p, f)) => (p, Some(f)),
+            None => (url, None),
+        };
+        // Drop any query string
+        let path_part = path_part.split('?').next().unwrap_or_default();
+        let path_part = percent_decode(path_part);
+
+        let target = if path_part.is_empty() {
+            source.to_path_buf()
+        } else if let Some(stripped) = path_part.strip_prefix('/') {
+            self.root.join(stripped)
+        } else {
+            source.parent().unwrap_or(&self.root).join(&path_part)
+        };
+
+        let kind = if is_image { "image" } else { "link target" };
+        if !target.exists() {
+            return Some(format!("{} not found: {}", kind, url));
+        }
+
+        let fragment = fragment.filter(|f| !f.is_empty())?;
+        if target.is_dir() {
+            return None;
+        }
+        let kind = match self.options.file_types.kind_of(&target) {
+            Some(kind @ (DocKind::Markdown | DocKind::Mdx)) => kind,
+            _ => return None,
+        };
+
+        let fragment = percent_decode(fragment).to_lowercase();
+        let slugs = if target == source {
+            document_slugs(content, kind)
+        } else {
+            self.slugs_for(&target, kind)
+        };
+
+        if slugs.contains(&fragment) {
+            None
+        } else {
+            Some(format!("anchor not found: {}", url))
+        }
+    }
+
+    fn slugs_for(&mut self, path: &Path, kind: DocKind) -> Vec<String> {
+        self.slug_cache
+            .entry(path.to_path_buf())
+            .or_insert_with(|| {
+                fs::read_to_string(path)
+                    .map(|content| document_slugs(&content, kind))
+                    .unwrap_or_default()
+            })
+            .clone()
+    }
+
+    fn check_url(&mut self, url: &str) -> Option<String> {
+        self.url_cache
+            .entry(url.to_string())
+            .or_insert_with(|| {
+                let agent = ureq::AgentBuilder::new()
+                    .timeout(Duration::from_secs(10))
+                    .build();
+                let result = match agent.head(url).call() {
+                    // Some servers reject HEAD, retry those with GET
+                    Err(ureq::Error::Status(405, _)) => agent.get(url).call(),
+                    other => other,
+                };
+                match result {
+                    Ok(_) => None,
+                    Err(ureq::Error::Status(code, _)) => {
+                        Some(format!("external link returned {}: {}", code, url))
+                    }
+                    Err(e) => Some(format!("external link failed: {} ({})", url, e)),
+                }
+            })
+            .clone()
+    }
+
+    fn report(&mut self, path: &Path, line: usize, message: String) {
+        self.diagnostics.push(Diagnostic {
+            path: path.to_path_buf(),
+            line,
+            message,
+        });
+    }
+}
+
+pub fn is_external(url: &str) -> bool {
+    if url.starts_with("//") {
+        return true;
+    }
+    // Anything with a URI scheme (http:, mailto:, ...) is not a local path
+    match url.find(':') {
+        Some(idx) => {
+            let scheme = &url[..idx];
+            !scheme.is_empty()
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+        }
+        None => false,
+    }
+}
+
+fn document_slugs(content: &str, kind: DocKind) -> Vec<String> {
+    match kind {
+        DocKind::Mdx => heading_slugs(&strip_jsx(content)),
+        _ => heading_slugs(content),
+    }
+}
+
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = (hex_value(bytes[i + 1]), hex_value(bytes[i + 2]));
+            if let (Some(hi), Some(lo)) = hex {
+                out.push(hi * 16 + lo);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|d| d as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn email_autolinks_are_not_files() {
+        let root = std::env::temp_dir().join(format!("rmd-test-{}-check-email", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("a.md"), "# A\n\nMail <me@example.com> or see [b](b.md).\n").unwrap();
+
+        let diagnostics = check_links(&root, &CheckOptions::default());
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, ["link target not found: b.md"]);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
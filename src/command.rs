@@ -0,0 +1,206 @@
+use std::fs;
+use std::path::Path;
+
+use crate::app::{Theme, WrapWidth};
+use crate::filetype::FileTypes;
+
+/// Command names offered by tab completion
+const COMMANDS: &[&str] = &["open", "quit", "set", "theme"];
+
+/// Options offered by tab completion after `:set`
+const SET_OPTIONS: &[&str] = &[
+    "number", "nonumber", "nowrap", "theme=dark", "theme=light", "wrap=120", "wrap=80", "wrap=off",
+];
+
+/// A parsed `:` command
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExCommand {
+    GoToLine(usize),
+    Open(String),
+    Set(SetOption),
+    Quit,
+}
+
+/// A setting changed with `:set` or `:theme`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SetOption {
+    LineNumbers(bool),
+    Wrap(WrapWidth),
+    Theme(Theme),
+}
+
+/// Parse the text typed after `:`
+pub fn parse(input: &str) -> Result<ExCommand, String> {
+    let input = input.trim();
+    let (name, arg) = match input.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (input, ""),
+    };
+
+    if let Ok(line) = name.parse::<usize>() {
+        return Ok(ExCommand::GoToLine(line));
+    }
+
+    match name {
+        "q" | "quit" | "q!" | "qa" => Ok(ExCommand::Quit),
+        "e" | "edit" | "o" | "open" => {
+            if arg.is_empty() {
+                Err("open: missing file path".to_string())
+            } else {
+                Ok(ExCommand::Open(arg.to_string()))
+            }
+        }
+        "set" | "se" => parse_set(arg).map(ExCommand::Set),
+        "theme" => parse_theme(arg).map(|t| ExCommand::Set(SetOption::Theme(t))),
+        "" => Err(String::new()),
+        _ => Err(format!("Not a command: {}", name)),
+    }
+}
+
+fn parse_set(arg: &str) -> Result<SetOption, String> {
+    let (key, value) = match arg.split_once('=') {
+        Some((key, value)) => (key.trim(), Some(value.trim())),
+        None => (arg, None),
+    };
+
+    match (key, value) {
+        ("number" | "nu", None) => Ok(SetOption::LineNumbers(true)),
+        ("nonumber" | "nonu", None) => Ok(SetOption::LineNumbers(false)),
+        ("nowrap", None) => Ok(SetOption::Wrap(WrapWidth::NoWrap)),
+        ("wrap", Some(value)) => match value {
+            "80" => Ok(SetOption::Wrap(WrapWidth::Chars80)),
+            "120" => Ok(SetOption::Wrap(WrapWidth::Chars120)),
+            "off" | "none" | "0" => Ok(SetOption::Wrap(WrapWidth::NoWrap)),
+            _ => Err(format!("set: wrap must be 80, 120 or off, not '{}'", value)),
+        },
+        ("theme", Some(value)) => parse_theme(value).map(SetOption::Theme),
+        ("", _) => Err("set: missing option".to_string()),
+        _ => Err(format!("set: unknown option '{}'", arg)),
+    }
+}
+
+fn parse_theme(arg: &str) -> Result<Theme, String> {
+    match arg {
+        "dark" => Ok(Theme::Dark),
+        "light" => Ok(Theme::Light),
+        _ => Err(format!("theme: expected dark or light, not '{}'", arg)),
+    }
+}
+
+/// Complete the command line, returning every full line that matches
+pub fn complete(input: &str, root: &Path, file_types: &FileTypes) -> Vec<String> {
+    let Some((name, arg)) = input.split_once(' ') else {
+        return COMMANDS
+            .iter()
+            .filter(|c| c.starts_with(input))
+            .map(|c| format!("{} ", c))
+            .collect();
+    };
+
+    let candidates: Vec<String> = match name {
+        "e" | "edit" | "o" | "open" => complete_path(arg, root, file_types),
+        "set" | "se" => complete_words(arg, SET_OPTIONS),
+        "theme" => complete_words(arg, &["dark", "light"]),
+        _ => Vec::new(),
+    };
+
+    candidates
+        .into_iter()
+        .map(|c| format!("{} {}", name, c))
+        .collect()
+}
+
+fn complete_words(arg: &str, words: &[&str]) -> Vec<String> {
+    words
+        .iter()
+        .filter(|w| w.starts_with(arg))
+        .map(|w| w.to_string())
+        .collect()
+}
+
+/// Complete a file path relative to the root, listing directories and documents
+fn complete_path(arg: &str, root: &Path, file_types: &FileTypes) -> Vec<String> {
+    let (dir_part, file_prefix) = match arg.rfind('/') {
+        Some(idx) => (&arg[..=idx], &arg[idx + 1..]),
+        None => ("", arg),
+    };
+
+    let dir = if Path::new(dir_part).is_absolute() {
+        Path::new(dir_part).to_path_buf()
+    } else {
+        root.join(dir_part)
+    };
+
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+
+    let mut candidates: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') && !file_prefix.starts_with('.') {
+                return None;
+            }
+            if !name.starts_with(file_prefix) {
+                return None;
+            }
+            let path = entry.path();
+            if path.is_dir() {
+                Some(format!("{}{}/", dir_part, name))
+            } else if file_types.is_document(&path) {
+                Some(format!("{}{}", dir_part, name))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    candidates.sort();
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_parse() {
+        let cases = [
+            ("42", Ok(ExCommand::GoToLine(42))),
+            (" q ", Ok(ExCommand::Quit)),
+            ("e notes/a.md", Ok(ExCommand::Open("notes/a.md".to_string()))),
+            ("set nu", Ok(ExCommand::Set(SetOption::LineNumbers(true)))),
+            ("set wrap=80", Ok(ExCommand::Set(SetOption::Wrap(WrapWidth::Chars80)))),
+            ("set wrap = off", Ok(ExCommand::Set(SetOption::Wrap(WrapWidth::NoWrap)))),
+            ("theme light", Ok(ExCommand::Set(SetOption::Theme(Theme::Light)))),
+            ("open", Err("open: missing file path".to_string())),
+            ("set wrap=100", Err("set: wrap must be 80, 120 or off, not '100'".to_string())),
+            ("frobnicate", Err("Not a command: frobnicate".to_string())),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(parse(input), expected, "input {:?}", input);
+        }
+    }
+
+    #[test]
+    fn completion_lists_commands_options_and_documents() {
+        let root = std::env::temp_dir().join(format!("rmd-test-{}-complete", std::process::id()));
+        fs::create_dir_all(root.join("notes")).unwrap();
+        for file in ["notes/a.md", "notes/b.txt", "notes/image.png", "readme.md"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+        let file_types = FileTypes::default();
+        let complete = |input: &str| complete(input, &root, &file_types);
+
+        assert_eq!(complete("s"), ["set "]);
+        assert_eq!(complete(""), ["open ", "quit ", "set ", "theme "]);
+        assert_eq!(complete("set wrap="), ["set wrap=120", "set wrap=80", "set wrap=off"]);
+        assert_eq!(complete("theme d"), ["theme dark"]);
+        assert_eq!(complete("e "), ["e notes/", "e readme.md"]);
+        assert_eq!(complete("open notes/"), ["open notes/a.md", "open notes/b.txt"]);
+        assert!(complete("quit x").is_empty());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
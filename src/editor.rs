@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+/// Arguments for opening `{file}` at `{line}`, for editors that don't take `+N`
+const LINE_ARGS: &[(&str, &str)] = &[
+    ("code", "--goto {file}:{line}"),
+    ("code-insiders", "--goto {file}:{line}"),
+    ("codium", "--goto {file}:{line}"),
+    ("cursor", "--goto {file}:{line}"),
+    ("hx", "{file}:{line}"),
+    ("helix", "{file}:{line}"),
+    ("subl", "{file}:{line}"),
+    ("zed", "{file}:{line}"),
+    ("idea", "--line {line} {file}"),
+];
+
+/// Understood by vi, vim, nvim, nano, emacs, kak, micro and most others
+const DEFAULT_LINE_ARGS: &str = "+{line} {file}";
+
+/// Build the command that opens `file` at `line` (1-based) in `$VISUAL` or `$EDITOR`.
+///
+/// `overrides` maps an editor's program name to its argument template, taking
+/// precedence over the built-in table.
+pub fn command(file: &Path, line: usize, overrides: &HashMap<String, String>) -> Option<Command> {
+    let editor = env::var("VISUAL")
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .or_else(|| env::var("EDITOR").ok())
+        .filter(|e| !e.trim().is_empty())?;
+
+    // The variable may carry its own arguments, e.g. "code --wait"
+    let mut words = editor.split_whitespace();
+    let program = words.next()?;
+    let name = Path::new(program)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let template = overrides
+        .get(&name)
+        .map(String::as_str)
+        .or_else(|| LINE_ARGS.iter().find(|(editor, _)| *editor == name).map(|(_, args)| *args))
+        .unwrap_or(DEFAULT_LINE_ARGS);
+
+    let mut command = Command::new(program);
+    command.args(words);
+    let file = file.to_string_lossy();
+    let line = line.to_string();
+    for arg in template.split_whitespace() {
+        command.arg(arg.replace("{file}", &file).replace("{line}", &line));
+    }
+    Some(command)
+}
@@ -1,6 +1,73 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use ignore::WalkBuilder;
+use serde::{Deserialize, Serialize};
 use walkdir::WalkDir;
 
+use crate::filetype::FileTypes;
+use crate::frontmatter::{self, FrontMatter};
+
+/// Project-level ignore file, using .gitignore syntax
+pub const IGNORE_FILENAME: &str = ".rmdignore";
+
+/// Order of entries within each directory (directories always come first)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum SortMode {
+    #[default]
+    Name,        // Case-insensitive name
+    Natural,     // Numbers compared by value: 2.md before 10.md
+    Modified,    // Newest first
+    Size,        // Largest first
+    FrontMatter, // Front-matter weight, then date (newest first), then name
+}
+
+impl SortMode {
+    pub fn display_name(self) -> &'static str {
+        match self {
+            SortMode::Name => "Name",
+            SortMode::Natural => "Natural",
+            SortMode::Modified => "Modified",
+            SortMode::Size => "Size",
+            SortMode::FrontMatter => "Front matter",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            SortMode::Name => SortMode::Natural,
+            SortMode::Natural => SortMode::Modified,
+            SortMode::Modified => SortMode::Size,
+            SortMode::Size => SortMode::FrontMatter,
+            SortMode::FrontMatter => SortMode::Name,
+        }
+    }
+}
+
+/// File metadata used for sorting and the sidebar columns
+#[derive(Debug, Clone, Default)]
+pub struct NodeMeta {
+    pub modified: Option<SystemTime>, // Newest descendant for directories
+    pub size: u64,                    // Total of descendants for directories
+    pub word_count: Option<usize>,    // Only loaded when needed
+    pub front_matter: Option<FrontMatter>,
+}
+
+impl NodeMeta {
+    /// Fold a document or subdirectory into a directory's totals
+    pub fn add(&mut self, other: &NodeMeta) {
+        self.modified = self.modified.max(other.modified);
+        self.size += other.size;
+        if let Some(words) = other.word_count {
+            *self.word_count.get_or_insert(0) += words;
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TreeNode {
     pub name: String,
@@ -8,6 +75,14 @@ pub struct TreeNode {
     pub is_dir: bool,
     pub depth: usize,
     pub expanded: bool,
+    pub ignored: bool, // Hidden or matched by an ignore file (only present when shown)
+    pub filtered_out: bool, // Hidden by the sidebar filter
+    pub loaded: bool,       // Children have been listed (directories load lazily)
+    pub scanning: bool,     // Not yet known whether the subtree contains any documents
+    pub symlink: bool,
+    pub error: Option<String>, // Why the entry could not be read, shown instead of hiding it
+    pub title: Option<String>, // Document title, only filled in while the sidebar shows titles
+    pub meta: NodeMeta,
     pub children: Vec<TreeNode>,
 }
 
@@ -19,10 +94,23 @@ impl TreeNode {
             is_dir,
             depth,
             expanded: depth == 0, // Root is expanded by default
+            ignored: false,
+            filtered_out: false,
+            loaded: false,
+            scanning: false,
+            symlink: false,
+            error: None,
+            title: None,
+            meta: NodeMeta::default(),
             children: Vec::new(),
         }
     }
 
+    /// The title if one is shown, otherwise the file name
+    pub fn label(&self) -> &str {
+        self.title.as_deref().unwrap_or(&self.name)
+    }
+
     /// Get all visible items in the tree (for rendering)
     pub fn visible_items(&self) -> Vec<&TreeNode> {
         let mut items = Vec::new();
@@ -33,7 +121,7 @@ impl TreeNode {
     fn collect_visible<'a>(&'a self, items: &mut Vec<&'a TreeNode>) {
         items.push(self);
         if self.expanded {
-            for child in &self.children {
+            for child in self.children.iter().filter(|c| !c.filtered_out) {
                 child.collect_visible(items);
             }
         }
@@ -63,7 +151,7 @@ impl TreeNode {
         *current_idx += 1;
 
         if self.expanded {
-            for child in &mut self.children {
+            for child in self.children.iter_mut().filter(|c| !c.filtered_out) {
                 if let Some(node) = child.find_by_index_recursive(current_idx, target_idx) {
                     return Some(node);
                 }
@@ -72,6 +160,120 @@ impl TreeNode {
         None
     }
 
+    /// Paths of all expanded directories, used to carry state across a rebuild
+    pub fn expanded_paths(&self) -> HashSet<PathBuf> {
+        let mut paths = HashSet::new();
+        self.collect_expanded(&mut paths);
+        paths
+    }
+
+    fn collect_expanded(&self, paths: &mut HashSet<PathBuf>) {
+        if self.is_dir && self.expanded {
+            paths.insert(self.path.clone());
+        }
+        for child in &self.children {
+            child.collect_expanded(paths);
+        }
+    }
+
+    /// Expand exactly the directories in `paths` (the root stays expanded)
+    pub fn restore_expanded(&mut self, paths: &HashSet<PathBuf>) {
+        if self.is_dir && self.depth > 0 {
+            self.expanded = paths.contains(&self.path);
+        }
+        for child in &mut self.children {
+            child.restore_expanded(paths);
+        }
+    }
+
+    /// Hide every file whose path (relative to `root`) or title does not contain all `terms`,
+    /// expanding the directories that lead to matches. Returns true if anything matched.
+    pub fn apply_filter(&mut self, root: &Path, terms: &[String]) -> bool {
+        if !self.is_dir {
+            let matched = matches_terms(root, &self.path, self.title.as_deref(), terms);
+            self.filtered_out = !matched;
+            return matched;
+        }
+
+        let mut any_match = false;
+        for child in &mut self.children {
+            any_match |= child.apply_filter(root, terms);
+        }
+        if self.depth > 0 {
+            self.filtered_out = !any_match;
+            if any_match {
+                self.expanded = true;
+            }
+        }
+        any_match
+    }
+
+    /// Show every node again
+    pub fn clear_filter(&mut self) {
+        self.filtered_out = false;
+        for child in &mut self.children {
+            child.clear_filter();
+        }
+    }
+
+    /// Find the node for `path` anywhere in the loaded tree
+    pub fn find_mut(&mut self, path: &Path) -> Option<&mut TreeNode> {
+        if self.path == path {
+            return Some(self);
+        }
+        if !self.is_dir || !path.starts_with(&self.path) {
+            return None;
+        }
+        self.children
+            .iter_mut()
+            .find(|c| path.starts_with(&c.path))
+            .and_then(|c| c.find_mut(path))
+    }
+
+    /// Remove the node for `path`, returning true if it was in the tree
+    pub fn remove(&mut self, path: &Path) -> bool {
+        let Some(parent) = path.parent() else {
+            return false;
+        };
+        match self.find_mut(parent) {
+            Some(node) => {
+                let before = node.children.len();
+                node.children.retain(|c| c.path != path);
+                node.children.len() != before
+            }
+            None => false,
+        }
+    }
+
+    /// Index of `path` among the visible items, if it is visible
+    pub fn visible_index_of(&self, path: &Path) -> Option<usize> {
+        self.visible_items().iter().position(|item| item.path == path)
+    }
+
+    /// Expand every directory leading to `path` and return its visible index
+    pub fn reveal(&mut self, path: &Path) -> Option<usize> {
+        if !self.expand_to(path) {
+            return None;
+        }
+        self.visible_index_of(path)
+    }
+
+    fn expand_to(&mut self, path: &Path) -> bool {
+        if self.path == path {
+            return true;
+        }
+        if !self.is_dir || !path.starts_with(&self.path) {
+            return false;
+        }
+        for child in &mut self.children {
+            if child.expand_to(path) {
+                self.expanded = true;
+                return true;
+            }
+        }
+        false
+    }
+
     /// Find parent index of a node at given index
     pub fn find_parent_index(&self, target_idx: usize) -> Option<usize> {
         let mut current_idx = 0;
@@ -92,7 +294,7 @@ impl TreeNode {
         *current_idx += 1;
 
         if self.expanded {
-            for child in &self.children {
+            for child in self.children.iter().filter(|c| !c.filtered_out) {
                 if let Some(idx) = child.find_parent_recursive(current_idx, target_idx, Some(my_idx)) {
                     return Some(idx);
                 }
@@ -102,65 +304,334 @@ impl TreeNode {
     }
 }
 
-/// Build a file tree from a directory, only including markdown files
-pub fn build_tree(root_path: &Path) -> TreeNode {
-    let root_name = root_path
-        .file_name()
-        .map(|s| s.to_string_lossy().to_string())
-        .unwrap_or_else(|| root_path.to_string_lossy().to_string());
+/// Whether every term occurs in the path (relative to `root`) or the title
+pub fn matches_terms(root: &Path, path: &Path, title: Option<&str>, terms: &[String]) -> bool {
+    let rel = path.strip_prefix(root).unwrap_or(path).to_string_lossy().to_lowercase();
+    let title = title.unwrap_or_default().to_lowercase();
+    terms.iter().all(|term| rel.contains(term.as_str()) || title.contains(term.as_str()))
+}
+
+/// Options controlling which entries `build_tree` includes
+#[derive(Debug, Clone, Default)]
+pub struct TreeOptions {
+    /// Include hidden and ignored entries, marked as `ignored`
+    pub show_ignored: bool,
+    /// Recognised document types
+    pub file_types: FileTypes,
+    /// Order of entries within each directory
+    pub sort_mode: SortMode,
+    /// Read file contents for word counts and front matter
+    pub load_contents: bool,
+}
 
-    let mut root = TreeNode::new(root_name, root_path.to_path_buf(), true, 0);
+/// Create the root node with only its immediate children listed.
+/// Deeper directories are listed by `list_dir` when they are expanded.
+pub fn load_root(root_path: &Path, options: &TreeOptions) -> TreeNode {
+    let mut root = TreeNode::new(root_name(root_path), root_path.to_path_buf(), true, 0);
+    match list_dir(root_path, 0, false, options) {
+        Ok(children) => root.children = children,
+        Err(error) => root.error = Some(error),
+    }
+    root.loaded = true;
+    sort_children(&mut root, options.sort_mode);
+    root
+}
 
-    // Collect all markdown files and directories
-    let mut entries: Vec<_> = WalkDir::new(root_path)
-        .min_depth(1)
+/// List the documents and subdirectories directly inside `dir`, or explain
+/// why it cannot be read.
+///
+/// Subdirectories are returned unloaded and marked `scanning`, since whether
+/// they contain any documents is only known once the background scan reaches them.
+/// Symlinks are followed, except where they lead back to a directory above them,
+/// including one reached through another link.
+pub fn list_dir(
+    dir: &Path,
+    depth: usize,
+    parent_ignored: bool,
+    options: &TreeOptions,
+) -> Result<Vec<TreeNode>, String> {
+    let listing = fs::read_dir(dir).map_err(|e| describe_io_error(&e))?;
+
+    // Entries that survive the ignore rules (including those of parent directories)
+    let included: HashSet<PathBuf> = WalkBuilder::new(dir)
+        .max_depth(Some(1))
+        .require_git(false)
+        .add_custom_ignore_filename(IGNORE_FILENAME)
+        .build()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.depth() > 0)
+        .map(|e| e.into_path())
+        .collect();
+
+    let mut entries: Vec<PathBuf> = if options.show_ignored {
+        listing.filter_map(|e| e.ok()).map(|e| e.path()).collect()
+    } else {
+        included.iter().cloned().collect()
+    };
+    entries.sort();
+
+    // Where each directory this one was expanded from really is
+    let expanded_from: Vec<PathBuf> = dir.ancestors().filter_map(|dir| fs::canonicalize(dir).ok()).collect();
+    let load_contents = options.load_contents || options.sort_mode == SortMode::FrontMatter;
+    let nodes = entries
         .into_iter()
-        .filter_entry(|e| {
-            // Skip hidden directories
-            !e.file_name()
-                .to_str()
-                .map(|s| s.starts_with('.'))
-                .unwrap_or(false)
+        .filter_map(|path| {
+            let symlink = fs::symlink_metadata(&path)
+                .map(|m| m.file_type().is_symlink())
+                .unwrap_or(false);
+            let error = match fs::metadata(&path) {
+                Ok(_) => None,
+                // Only dangling links that look like documents are worth showing
+                Err(_) if symlink && options.file_types.is_document(&path) => Some(broken_link_error(&path)),
+                Err(_) => return None,
+            };
+
+            let is_dir = path.is_dir();
+            if !is_dir && error.is_none() && !options.file_types.is_document(&path) {
+                return None;
+            }
+            let name = path.file_name()?.to_string_lossy().to_string();
+            let mut node = TreeNode::new(name, path.clone(), is_dir, depth + 1);
+            node.ignored = parent_ignored || !included.contains(&path);
+            node.symlink = symlink;
+            node.error = error;
+            if is_dir {
+                node.error = dir_error(&path, symlink, &expanded_from);
+                node.scanning = node.error.is_none();
+                // Nothing to list for a directory that cannot be opened
+                node.loaded = node.error.is_some();
+            } else if node.error.is_none() {
+                node.meta = file_meta(&path, load_contents);
+            }
+            Some(node)
         })
+        .collect();
+    Ok(nodes)
+}
+
+/// Why a subdirectory cannot be listed, if it can't
+fn dir_error(path: &Path, symlink: bool, expanded_from: &[PathBuf]) -> Option<String> {
+    if symlink {
+        if let Ok(target) = fs::canonicalize(path) {
+            if expanded_from.iter().any(|dir| dir.starts_with(&target)) {
+                return Some(format!("symlink loop: points back to {}", target.display()));
+            }
+        }
+    }
+    fs::read_dir(path).err().map(|e| describe_io_error(&e))
+}
+
+fn broken_link_error(path: &Path) -> String {
+    match fs::read_link(path) {
+        Ok(target) => format!("broken symlink: {} does not exist", target.display()),
+        Err(e) => describe_io_error(&e),
+    }
+}
+
+/// Short, user-facing description of a filesystem error
+pub fn describe_io_error(error: &io::Error) -> String {
+    match error.kind() {
+        io::ErrorKind::PermissionDenied => "permission denied".to_string(),
+        io::ErrorKind::NotFound => "not found".to_string(),
+        _ => error.to_string(),
+    }
+}
+
+fn root_name(root_path: &Path) -> String {
+    root_path
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| root_path.to_string_lossy().to_string())
+}
+
+/// Build a complete file tree from a directory, only including recognised documents.
+///
+/// Hidden entries and anything matched by `.gitignore`, `.ignore` or `.rmdignore`
+/// are skipped, unless `show_ignored` is set, in which case they are included
+/// and marked as `ignored`.
+pub fn build_tree(root_path: &Path, options: &TreeOptions) -> TreeNode {
+    let mut root = TreeNode::new(root_name(root_path), root_path.to_path_buf(), true, 0);
+
+    // Entries that survive the ignore rules
+    let included: HashSet<PathBuf> = WalkBuilder::new(root_path)
+        .follow_links(true)
+        .require_git(false)
+        .add_custom_ignore_filename(IGNORE_FILENAME)
+        .build()
         .filter_map(|e| e.ok())
-        .filter(|e| {
-            e.file_type().is_dir()
-                || e.path()
-                    .extension()
-                    .map(|ext| ext == "md" || ext == "markdown")
-                    .unwrap_or(false)
-        })
+        .filter(|e| e.depth() > 0)
+        .map(|e| e.into_path())
         .collect();
 
+    // Collect all markdown files and directories
+    let mut entries: Vec<(PathBuf, bool)> = if options.show_ignored {
+        WalkDir::new(root_path)
+            .min_depth(1)
+            .follow_links(true)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .map(|e| (e.path().to_path_buf(), e.file_type().is_dir()))
+            .collect()
+    } else {
+        included
+            .iter()
+            .map(|path| (path.clone(), path.is_dir()))
+            .collect()
+    };
+    entries.retain(|(path, is_dir)| *is_dir || options.file_types.is_document(path));
+
     // Sort entries by path for consistent ordering
-    entries.sort_by(|a, b| a.path().cmp(b.path()));
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
 
     // Build nested structure
-    for entry in entries {
-        let rel_path = entry.path().strip_prefix(root_path).unwrap();
+    for (path, is_dir) in entries {
+        let rel_path = path.strip_prefix(root_path).unwrap();
         let components: Vec<_> = rel_path.components().collect();
+        let ignored = !included.contains(&path);
 
-        insert_path(
-            &mut root,
-            entry.path(),
-            &components,
-            0,
-            entry.file_type().is_dir(),
-        );
+        insert_path(&mut root, &path, &components, 0, is_dir, ignored);
     }
 
     // Remove empty directories
     prune_empty_dirs(&mut root);
 
+    load_meta(&mut root, options.load_contents || options.sort_mode == SortMode::FrontMatter);
+    sort_tree(&mut root, options.sort_mode);
+
     root
 }
 
+/// Fill in metadata bottom-up, aggregating modification time and size for directories
+fn load_meta(node: &mut TreeNode, load_contents: bool) {
+    if !node.is_dir {
+        node.meta = file_meta(&node.path, load_contents);
+        return;
+    }
+
+    for child in &mut node.children {
+        load_meta(child, load_contents);
+    }
+    node.meta.modified = node.children.iter().filter_map(|c| c.meta.modified).max();
+    node.meta.size = node.children.iter().map(|c| c.meta.size).sum();
+    if load_contents {
+        node.meta.word_count = Some(node.children.iter().filter_map(|c| c.meta.word_count).sum());
+    }
+}
+
+/// Size, modification time and (optionally) contents-derived metadata of a document
+pub fn file_meta(path: &Path, load_contents: bool) -> NodeMeta {
+    let mut meta = NodeMeta::default();
+    if let Ok(metadata) = fs::metadata(path) {
+        meta.modified = metadata.modified().ok();
+        meta.size = metadata.len();
+    }
+    if load_contents {
+        if let Ok(content) = fs::read_to_string(path) {
+            meta.word_count = Some(content.split_whitespace().count());
+            meta.front_matter = frontmatter::parse(&content);
+        }
+    }
+    meta
+}
+
+/// Sort children recursively: directories first, then by the sort mode
+fn sort_tree(node: &mut TreeNode, mode: SortMode) {
+    sort_children(node, mode);
+    for child in &mut node.children {
+        sort_tree(child, mode);
+    }
+}
+
+/// Sort the immediate children of a node
+pub fn sort_children(node: &mut TreeNode, mode: SortMode) {
+    node.children.sort_by(|a, b| match (a.is_dir, b.is_dir) {
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        _ => compare_nodes(a, b, mode),
+    });
+}
+
+fn compare_nodes(a: &TreeNode, b: &TreeNode, mode: SortMode) -> Ordering {
+    // Titles, when shown, are what the sidebar is sorted by
+    let by_name = || a.label().to_lowercase().cmp(&b.label().to_lowercase());
+    match mode {
+        SortMode::Name => by_name(),
+        SortMode::Natural => natural_cmp(a.label(), b.label()),
+        SortMode::Modified => b.meta.modified.cmp(&a.meta.modified).then_with(by_name),
+        SortMode::Size => b.meta.size.cmp(&a.meta.size).then_with(by_name),
+        SortMode::FrontMatter => {
+            let weight = |n: &TreeNode| n.meta.front_matter.as_ref().and_then(|fm| fm.weight);
+            let date = |n: &TreeNode| n.meta.front_matter.as_ref().and_then(|fm| fm.date.clone());
+            // Entries with a weight come first (lowest first), then dated entries (newest first)
+            match (weight(a), weight(b)) {
+                (Some(wa), Some(wb)) => wa.cmp(&wb),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => match (date(a), date(b)) {
+                    (Some(da), Some(db)) => db.cmp(&da),
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => Ordering::Equal,
+                },
+            }
+            .then_with(|| natural_cmp(a.label(), b.label()))
+        }
+    }
+}
+
+/// Compare names with runs of digits ordered by numeric value
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(ca), Some(cb)) if ca.is_ascii_digit() && cb.is_ascii_digit() => {
+                let take_number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(&c) = chars.peek() {
+                        if !c.is_ascii_digit() {
+                            break;
+                        }
+                        digits.push(c);
+                        chars.next();
+                    }
+                    digits
+                };
+                let na = take_number(&mut a_chars);
+                let nb = take_number(&mut b_chars);
+                let na_trim = na.trim_start_matches('0');
+                let nb_trim = nb.trim_start_matches('0');
+                let ord = na_trim
+                    .len()
+                    .cmp(&nb_trim.len())
+                    .then_with(|| na_trim.cmp(nb_trim));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+            }
+            (Some(ca), Some(cb)) => {
+                let ord = ca.to_lowercase().cmp(cb.to_lowercase());
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
+}
+
 fn insert_path(
     node: &mut TreeNode,
     full_path: &Path,
     components: &[std::path::Component],
     depth: usize,
     is_dir: bool,
+    ignored: bool,
 ) {
     if components.is_empty() {
         return;
@@ -174,16 +645,10 @@ fn insert_path(
     if components.len() == 1 {
         // This is the target node
         if child_idx.is_none() {
-            let child = TreeNode::new(name, full_path.to_path_buf(), is_dir, depth + 1);
+            let mut child = TreeNode::new(name, full_path.to_path_buf(), is_dir, depth + 1);
+            child.ignored = ignored;
+            // Children are sorted once the whole tree is built
             node.children.push(child);
-            // Sort children: directories first, then alphabetically
-            node.children.sort_by(|a, b| {
-                match (a.is_dir, b.is_dir) {
-                    (true, false) => std::cmp::Ordering::Less,
-                    (false, true) => std::cmp::Ordering::Greater,
-                    _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
-                }
-            });
         }
     } else {
         // Need to traverse deeper
@@ -200,11 +665,13 @@ fn insert_path(
             node.children.last_mut().unwrap()
         };
 
-        insert_path(child, full_path, &components[1..], depth + 1, is_dir);
+        insert_path(child, full_path, &components[1..], depth + 1, is_dir, ignored);
     }
 }
 
 fn prune_empty_dirs(node: &mut TreeNode) {
+    node.loaded = true;
+
     // Recursively prune children first
     for child in &mut node.children {
         prune_empty_dirs(child);
@@ -215,3 +682,33 @@ fn prune_empty_dirs(node: &mut TreeNode) {
         !child.is_dir || !child.children.is_empty()
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listed_error(dir: &Path, name: &str) -> Option<String> {
+        let nodes = list_dir(dir, 0, false, &TreeOptions::default()).unwrap();
+        nodes.into_iter().find(|node| node.name == name).unwrap().error
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sibling_links_to_each_other_are_a_loop() {
+        let root = std::env::temp_dir().join(format!("rmd-test-{}-mutual-links", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("a")).unwrap();
+        fs::create_dir_all(root.join("b")).unwrap();
+        std::os::unix::fs::symlink("../b", root.join("a/l")).unwrap();
+        std::os::unix::fs::symlink("../a", root.join("b/l")).unwrap();
+        std::os::unix::fs::symlink("..", root.join("a/up")).unwrap();
+
+        // a/l leads to b, which was not expanded on the way
+        assert_eq!(listed_error(&root.join("a"), "l"), None);
+        assert!(listed_error(&root.join("a"), "up").is_some_and(|e| e.starts_with("symlink loop")));
+        // a/l/l leads back to a
+        assert!(listed_error(&root.join("a/l"), "l").is_some_and(|e| e.starts_with("symlink loop")));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
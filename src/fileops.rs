@@ -0,0 +1,297 @@
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+use pulldown_cmark::{Event, LinkType, Options, Parser, Tag};
+
+use crate::check::{is_external, percent_decode};
+use crate::util::format_date;
+
+/// A sidebar file operation waiting for input or confirmation
+#[derive(Debug, Clone)]
+pub enum FileOp {
+    /// Create a file in the directory, optionally from a template
+    NewFile { dir: PathBuf, template: Option<usize> },
+    /// Create a directory in the directory, optionally from a template
+    NewDir { dir: PathBuf, template: Option<usize> },
+    /// Rename within the same directory
+    Rename(PathBuf),
+    /// Move to a path relative to the root
+    Move(PathBuf),
+    /// Delete after a y/n confirmation
+    Delete(PathBuf),
+    /// Offer to rewrite links in `documents` after `from` was renamed to `to`
+    UpdateLinks {
+        from: PathBuf,
+        to: PathBuf,
+        documents: Vec<PathBuf>,
+    },
+}
+
+impl FileOp {
+    /// Whether the operation is answered with y/n rather than typed input
+    pub fn is_confirmation(&self) -> bool {
+        matches!(self, FileOp::Delete(_) | FileOp::UpdateLinks { .. })
+    }
+}
+
+/// Templates for new files and directories: the files and directories in
+/// `~/.config/rmd/templates`, in name order
+pub fn templates(dirs: bool) -> Vec<PathBuf> {
+    let Some(config_dir) = dirs::config_dir() else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(config_dir.join("rmd").join("templates")) else {
+        return Vec::new();
+    };
+    let mut templates: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_dir() == dirs)
+        .filter(|p| !p.file_name().unwrap_or_default().to_string_lossy().starts_with('.'))
+        .collect();
+    templates.sort();
+    templates
+}
+
+/// Create a new document, filling `{{title}}` and `{{date}}` in the template
+pub fn create_file(path: &Path, template: Option<&Path>) -> io::Result<()> {
+    let content = match template {
+        Some(template) => fs::read_to_string(template)?,
+        None => "# {{title}}\n".to_string(),
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = fs::OpenOptions::new().write(true).create_new(true).open(path)?;
+    io::Write::write_all(&mut file, fill_template(&content, &title_for(path)).as_bytes())
+}
+
+/// Create a new directory, copying the template directory's contents into it
+pub fn create_dir(path: &Path, template: Option<&Path>) -> io::Result<()> {
+    if path.exists() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, "already exists"));
+    }
+    fs::create_dir_all(path)?;
+    if let Some(template) = template {
+        copy_template_dir(template, path, &title_for(path))?;
+    }
+    Ok(())
+}
+
+fn copy_template_dir(from: &Path, to: &Path, title: &str) -> io::Result<()> {
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            fs::create_dir_all(&target)?;
+            copy_template_dir(&entry.path(), &target, title)?;
+        } else {
+            let content = fs::read_to_string(entry.path())?;
+            fs::write(&target, fill_template(&content, title))?;
+        }
+    }
+    Ok(())
+}
+
+/// Title derived from a new entry's name: `my-first_post.md` becomes "my first post"
+fn title_for(path: &Path) -> String {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    stem.replace(['-', '_'], " ")
+}
+
+fn fill_template(content: &str, title: &str) -> String {
+    content
+        .replace("{{title}}", title)
+        .replace("{{date}}", &format_date(SystemTime::now()))
+}
+
+/// Rename or move a file or directory, refusing to overwrite anything
+pub fn rename(from: &Path, to: &Path) -> io::Result<()> {
+    if to.exists() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", to.display())));
+    }
+    if to.starts_with(from) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot move a directory into itself"));
+    }
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(from, to)
+}
+
+pub fn delete(path: &Path) -> io::Result<()> {
+    if path.is_dir() && !fs::symlink_metadata(path)?.file_type().is_symlink() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Documents with relative links to `from` or anything inside it
+pub fn documents_linking_to(documents: &[PathBuf], root: &Path, from: &Path) -> Vec<PathBuf> {
+    documents
+        .iter()
+        .filter(|doc| {
+            fs::read_to_string(doc)
+                .map(|content| !link_edits(&content, doc, root, from, from).is_empty())
+                .unwrap_or(false)
+        })
+        .cloned()
+        .collect()
+}
+
+/// Point links in `document` that led to `from` at `to` instead
+pub fn rewrite_links(document: &Path, root: &Path, from: &Path, to: &Path) -> io::Result<()> {
+    let mut content = fs::read_to_string(document)?;
+    let mut edits = link_edits(&content, document, root, from, to);
+    // Apply from the end so earlier ranges stay valid
+    edits.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+    for (range, replacement) in edits {
+        content.replace_range(range, &replacement);
+    }
+    fs::write(document, content)
+}
+
+/// Source ranges of link destinations pointing into `from`, with their replacements
+fn link_edits(content: &str, document: &Path, root: &Path, from: &Path, to: &Path) -> Vec<(Range<usize>, String)> {
+    let mut destinations = Vec::new();
+    let mut parser = Parser::new_ext(content, Options::ENABLE_TABLES).into_offset_iter();
+    for (event, range) in parser.by_ref() {
+        // Reference-style links are rewritten through their definitions below
+        match event {
+            Event::Start(Tag::Link { link_type: LinkType::Inline, dest_url, .. })
+            | Event::Start(Tag::Image { link_type: LinkType::Inline, dest_url, .. }) => {
+                destinations.push((dest_url.to_string(), range, "]("));
+            }
+            _ => {}
+        }
+    }
+    for (_, def) in parser.reference_definitions().iter() {
+        destinations.push((def.dest.to_string(), def.span.clone(), "]:"));
+    }
+
+    let doc_dir = document.parent().unwrap_or(root);
+    destinations
+        .into_iter()
+        .filter_map(|(url, range, opener)| {
+            let new_url = rewrite_url(&url, doc_dir, root, from, to)?;
+            let start = range.start + destination_offset(&content[range], &url, opener)?;
+            Some((start..start + url.len(), new_url))
+        })
+        .collect()
+}
+
+/// Where `url` is in a link's source: right after the `](` (or `]:` of a
+/// definition) that ends the link text, not in a title after it. The last
+/// such opener is the link's own, since the text can hold an image.
+fn destination_offset(source: &str, url: &str, opener: &str) -> Option<usize> {
+    source.rmatch_indices(opener).find_map(|(idx, _)| {
+        let after = source[idx + opener.len()..].trim_start();
+        let after = after.strip_prefix('<').unwrap_or(after);
+        after.starts_with(url).then(|| source.len() - after.len())
+    })
+}
+
+/// The new URL for a link that pointed into `from`, or None if it points elsewhere
+fn rewrite_url(url: &str, doc_dir: &Path, root: &Path, from: &Path, to: &Path) -> Option<String> {
+    if url.is_empty() || url.starts_with('#') || is_external(url) {
+        return None;
+    }
+    let split = url.find(['#', '?']).unwrap_or(url.len());
+    let (path_part, suffix) = url.split_at(split);
+
+    let decoded = percent_decode(path_part);
+    let target = match decoded.strip_prefix('/') {
+        Some(rooted) => normalize(&root.join(rooted)),
+        None => normalize(&doc_dir.join(&decoded)),
+    };
+    let rest = target.strip_prefix(from).ok()?;
+    let new_target = if rest.as_os_str().is_empty() { to.to_path_buf() } else { to.join(rest) };
+
+    let mut new_path = if path_part.starts_with('/') {
+        format!("/{}", new_target.strip_prefix(root).ok()?.to_string_lossy())
+    } else {
+        let relative = relative_path(doc_dir, &new_target).to_string_lossy().to_string();
+        // Keep the link's own style where it still applies
+        if path_part.starts_with("./") && !relative.starts_with("..") {
+            format!("./{}", relative)
+        } else {
+            relative
+        }
+    };
+    if path_part.ends_with('/') && !new_path.ends_with('/') {
+        new_path.push('/');
+    }
+    Some(format!("{}{}", new_path.replace(' ', "%20"), suffix))
+}
+
+/// Resolve `.` and `..` components without touching the filesystem
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// `path` with `.` and `..` resolved, unless that takes it outside `root`
+pub fn within_root(root: &Path, path: &Path) -> Option<PathBuf> {
+    let path = normalize(path);
+    path.starts_with(normalize(root)).then_some(path)
+}
+
+/// Path to `to` relative to the directory `from_dir`
+fn relative_path(from_dir: &Path, to: &Path) -> PathBuf {
+    let from: Vec<Component> = from_dir.components().collect();
+    let to: Vec<Component> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut out = PathBuf::new();
+    for _ in common..from.len() {
+        out.push("..");
+    }
+    for component in &to[common..] {
+        out.push(component);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewritten(content: &str) -> String {
+        let root = Path::new("/docs");
+        let mut content = content.to_string();
+        let mut edits = link_edits(&content, &root.join("index.md"), root, &root.join("a.md"), &root.join("b.md"));
+        edits.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+        for (range, replacement) in edits {
+            content.replace_range(range, &replacement);
+        }
+        content
+    }
+
+    #[test]
+    fn only_link_destinations_are_rewritten() {
+        let cases = [
+            ("[a](a.md)", "[a](b.md)".to_string()),
+            (r#"[a](a.md "a.md")"#, r#"[a](b.md "a.md")"#.to_string()),
+            ("[a.md](<a.md> 'a.md')", "[a.md](<b.md> 'a.md')".to_string()),
+            ("[![a](a.md)](a.md#top)", "[![a](b.md)](b.md#top)".to_string()),
+            ("[a][ref]\n\n[ref]: a.md \"a.md\"\n", "[a][ref]\n\n[ref]: b.md \"a.md\"\n".to_string()),
+            ("[c](c.md \"a.md\")", "[c](c.md \"a.md\")".to_string()),
+        ];
+        for (content, expected) in cases {
+            assert_eq!(rewritten(content), expected, "content {:?}", content);
+        }
+    }
+}
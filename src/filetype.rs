@@ -0,0 +1,259 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::markdown::{render_markdown, render_plain_text, RenderedLine};
+
+/// How a recognised document is rendered
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DocKind {
+    Markdown,
+    Mdx,       // Markdown with JSX, stripped before rendering
+    PlainText, // Shown verbatim
+}
+
+/// Which files show up in the sidebar, configured in settings.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileTypes {
+    pub markdown_extensions: Vec<String>,
+    pub mdx_extensions: Vec<String>,
+    pub plain_text_extensions: Vec<String>,
+    /// Exact file names rendered as Markdown (e.g. an extensionless README)
+    pub filenames: Vec<String>,
+    /// Look inside other extensionless files for Markdown syntax
+    pub sniff_extensionless: bool,
+}
+
+impl Default for FileTypes {
+    fn default() -> Self {
+        Self {
+            markdown_extensions: ["md", "markdown", "mdown", "mkd", "mkdn"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            mdx_extensions: vec!["mdx".to_string()],
+            plain_text_extensions: vec!["txt".to_string()],
+            filenames: ["README", "CHANGELOG", "CONTRIBUTING"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            sniff_extensionless: true,
+        }
+    }
+}
+
+impl FileTypes {
+    /// Classify a file, returning None if it is not a recognised document
+    pub fn kind_of(&self, path: &Path) -> Option<DocKind> {
+        let name = path.file_name()?.to_string_lossy();
+        if self.filenames.iter().any(|f| f.eq_ignore_ascii_case(&name)) {
+            return Some(DocKind::Markdown);
+        }
+
+        match path.extension() {
+            Some(ext) => {
+                let ext = ext.to_string_lossy().to_lowercase();
+                if self.markdown_extensions.iter().any(|e| e.eq_ignore_ascii_case(&ext)) {
+                    Some(DocKind::Markdown)
+                } else if self.mdx_extensions.iter().any(|e| e.eq_ignore_ascii_case(&ext)) {
+                    Some(DocKind::Mdx)
+                } else if self.plain_text_extensions.iter().any(|e| e.eq_ignore_ascii_case(&ext)) {
+                    Some(DocKind::PlainText)
+                } else {
+                    None
+                }
+            }
+            None if self.sniff_extensionless && !name.starts_with('.') && looks_like_markdown(path) => {
+                Some(DocKind::Markdown)
+            }
+            None => None,
+        }
+    }
+
+    pub fn is_document(&self, path: &Path) -> bool {
+        self.kind_of(path).is_some()
+    }
+}
+
+/// Render file contents according to their kind
+pub fn render_document(content: &str, kind: DocKind) -> Vec<RenderedLine> {
+    match kind {
+        DocKind::Markdown => render_markdown(content),
+        DocKind::Mdx => {
+            // Point lines back at the MDX source, past the lines stripped from it
+            let (markdown, source_lines) = strip_jsx_mapped(content);
+            let mut lines = render_markdown(&markdown);
+            for line in &mut lines {
+                line.source_line = source_lines.get(line.source_line).copied().unwrap_or(line.source_line);
+            }
+            lines
+        }
+        DocKind::PlainText => render_plain_text(content),
+    }
+}
+
+/// Check the start of an extensionless file for Markdown syntax
+fn looks_like_markdown(path: &Path) -> bool {
+    let Ok(mut file) = File::open(path) else {
+        return false;
+    };
+    let mut buf = [0u8; 4096];
+    let Ok(len) = file.read(&mut buf) else {
+        return false;
+    };
+    let buf = &buf[..len];
+    if buf.contains(&0) {
+        return false; // Binary file
+    }
+
+    // The buffer may end in the middle of a character
+    let text = match std::str::from_utf8(buf) {
+        Ok(text) => text,
+        Err(e) if e.valid_up_to() > 0 && len == 4096 => {
+            std::str::from_utf8(&buf[..e.valid_up_to()]).unwrap_or_default()
+        }
+        Err(_) => return false,
+    };
+
+    text.starts_with("---\n")
+        || text.lines().any(|line| {
+            let line = line.trim_end();
+            (line.starts_with('#') && line.trim_start_matches('#').starts_with(' '))
+                || line.starts_with("```")
+                || line.starts_with("- [")
+                || (line.len() >= 3 && line.chars().all(|c| c == '='))
+        })
+}
+
+/// Remove MDX-only syntax (ESM imports/exports, JSX tags and `{expressions}`)
+/// so the rest can be rendered as plain Markdown
+pub fn strip_jsx(content: &str) -> String {
+    strip_jsx_mapped(content).0
+}
+
+/// `strip_jsx`, along with the source line each line of the result came from
+fn strip_jsx_mapped(content: &str) -> (String, Vec<usize>) {
+    let mut out = String::with_capacity(content.len());
+    let mut source_lines = Vec::new();
+    let mut in_fence = false;
+    let mut esm_depth: Option<i32> = None; // Brace depth of an unfinished import/export
+    let mut in_tag = false; // Inside a JSX tag spanning several lines
+    let mut expr_depth = 0; // Inside a `{...}` expression spanning several lines
+
+    for (idx, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            out.push_str(line);
+            out.push('\n');
+            source_lines.push(idx);
+            continue;
+        }
+        if in_fence {
+            out.push_str(line);
+            out.push('\n');
+            source_lines.push(idx);
+            continue;
+        }
+
+        if let Some(depth) = esm_depth {
+            let depth = depth + brace_balance(line);
+            esm_depth = if depth > 0 { Some(depth) } else { None };
+            continue;
+        }
+        if !in_tag && expr_depth == 0 && (trimmed.starts_with("import ") || trimmed.starts_with("export ")) {
+            let depth = brace_balance(line);
+            if depth > 0 {
+                esm_depth = Some(depth);
+            }
+            continue;
+        }
+
+        let stripped = strip_jsx_line(line, &mut in_tag, &mut expr_depth);
+        // Drop lines that only held JSX, but keep real blank lines
+        if stripped.trim().is_empty() && !line.trim().is_empty() {
+            continue;
+        }
+        out.push_str(&stripped);
+        out.push('\n');
+        source_lines.push(idx);
+    }
+
+    (out, source_lines)
+}
+
+fn brace_balance(line: &str) -> i32 {
+    line.chars().fold(0, |acc, c| match c {
+        '{' => acc + 1,
+        '}' => acc - 1,
+        _ => acc,
+    })
+}
+
+/// Strip JSX tags and expressions from one line, skipping inline code spans
+fn strip_jsx_line(line: &str, in_tag: &mut bool, expr_depth: &mut usize) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let mut out = String::with_capacity(line.len());
+    let mut i = 0;
+    let mut in_code = false;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if *in_tag {
+            if c == '>' {
+                *in_tag = false;
+            }
+            i += 1;
+            continue;
+        }
+        if *expr_depth > 0 {
+            match c {
+                '{' => *expr_depth += 1,
+                '}' => *expr_depth -= 1,
+                _ => {}
+            }
+            i += 1;
+            continue;
+        }
+
+        if c == '`' {
+            in_code = !in_code;
+            out.push(c);
+            i += 1;
+            continue;
+        }
+        if in_code {
+            out.push(c);
+            i += 1;
+            continue;
+        }
+
+        if c == '{' {
+            *expr_depth = 1;
+            i += 1;
+            continue;
+        }
+
+        // JSX components start with a capital letter; fragments are `<>` / `</>`
+        if c == '<' {
+            let next = chars.get(i + 1).copied();
+            let after_slash = if next == Some('/') { chars.get(i + 2).copied() } else { next };
+            let is_jsx = matches!(after_slash, Some(ch) if ch.is_ascii_uppercase() || ch == '>');
+            if is_jsx {
+                *in_tag = true;
+                i += 1;
+                continue;
+            }
+        }
+
+        out.push(c);
+        i += 1;
+    }
+
+    out
+}
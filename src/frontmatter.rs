@@ -0,0 +1,101 @@
+/// The few front-matter fields rmd understands
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrontMatter {
+    pub title: Option<String>,
+    pub date: Option<String>,
+    pub weight: Option<i64>,
+    pub tags: Vec<String>,
+}
+
+/// Parse YAML (`---`) or TOML (`+++`) front matter at the start of a document.
+///
+/// Only flat `key: value` / `key = value` lines are read, which covers the
+/// fields above without pulling in a full YAML parser.
+pub fn parse(content: &str) -> Option<FrontMatter> {
+    let (body, separator, _) = front_matter_block(content)?;
+    let mut fm = FrontMatter::default();
+    let mut in_tag_list = false;
+
+    for line in body.lines() {
+        // YAML block list continuation: "  - tag"
+        if in_tag_list {
+            if let Some(item) = line.trim_start().strip_prefix("- ") {
+                fm.tags.push(unquote(item).to_string());
+                continue;
+            }
+            in_tag_list = false;
+        }
+
+        let Some((key, value)) = line.split_once(separator) else {
+            continue;
+        };
+        if key.starts_with(char::is_whitespace) {
+            continue; // Nested key
+        }
+        let value = value.trim();
+
+        match key.trim().to_lowercase().as_str() {
+            "title" => fm.title = Some(unquote(value).to_string()).filter(|t| !t.is_empty()),
+            "date" => fm.date = Some(unquote(value).to_string()).filter(|d| !d.is_empty()),
+            "weight" => fm.weight = unquote(value).parse().ok(),
+            "tags" | "keywords" => {
+                if value.is_empty() {
+                    in_tag_list = true;
+                } else {
+                    fm.tags = value
+                        .trim_start_matches('[')
+                        .trim_end_matches(']')
+                        .split(',')
+                        .map(|t| unquote(t.trim()).to_string())
+                        .filter(|t| !t.is_empty())
+                        .collect();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Some(fm)
+}
+
+/// The document after its front-matter block, or all of it if it has none
+pub fn strip(content: &str) -> &str {
+    front_matter_block(content).map(|(_, _, rest)| rest).unwrap_or(content)
+}
+
+/// Split off the front-matter block, returning its body, key/value separator
+/// and the rest of the document
+fn front_matter_block(content: &str) -> Option<(&str, char, &str)> {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let (fence, separator) = if content.starts_with("---") {
+        ("---", ':')
+    } else if content.starts_with("+++") {
+        ("+++", '=')
+    } else {
+        return None;
+    };
+
+    let first_newline = content.find('\n')?;
+    if content[..first_newline].trim_end() != fence {
+        return None;
+    }
+    let rest = &content[first_newline + 1..];
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == fence {
+            return Some((&rest[..offset], separator, &rest[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+        .unwrap_or(value)
+}
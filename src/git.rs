@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+
+/// Git status of a file, or the most notable status below a directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GitStatus {
+    Untracked,
+    Added,
+    Modified,
+}
+
+impl GitStatus {
+    pub fn marker(self) -> char {
+        match self {
+            GitStatus::Untracked => '?',
+            GitStatus::Added => 'A',
+            GitStatus::Modified => 'M',
+        }
+    }
+}
+
+/// Statuses of changed files under the root, rolled up to their directories
+#[derive(Debug, Clone, Default)]
+pub struct GitStatuses {
+    statuses: HashMap<PathBuf, GitStatus>,
+}
+
+impl GitStatuses {
+    pub fn is_empty(&self) -> bool {
+        self.statuses.is_empty()
+    }
+
+    pub fn get(&self, path: &Path) -> Option<GitStatus> {
+        self.statuses.get(path).copied()
+    }
+
+    /// Record a file's status on it and every directory up to the root
+    fn insert(&mut self, root: &Path, path: PathBuf, status: GitStatus) {
+        for ancestor in path.ancestors().take_while(|p| p.starts_with(root)) {
+            let entry = self.statuses.entry(ancestor.to_path_buf()).or_insert(status);
+            *entry = (*entry).max(status);
+        }
+    }
+}
+
+/// Run `git status` for `root` in the background. The receiver yields None
+/// if the root is not inside a git work tree or git is not available.
+pub fn load_in_background(root: &Path) -> Receiver<Option<GitStatuses>> {
+    let (tx, rx) = channel();
+    let root = root.to_path_buf();
+    thread::spawn(move || {
+        let _ = tx.send(load(&root));
+    });
+    rx
+}
+
+fn load(root: &Path) -> Option<GitStatuses> {
+    let toplevel = git(root, &["rev-parse", "--show-toplevel"])?;
+    let toplevel = PathBuf::from(toplevel.trim_end());
+    let output = git(root, &["status", "--porcelain=v1", "-z", "--untracked-files=all", "--", "."])?;
+
+    // Git reports paths relative to the top of the work tree, with symlinks resolved
+    let canonical_root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    let mut statuses = GitStatuses::default();
+
+    let mut records = output.split('\0');
+    while let Some(record) = records.next() {
+        if record.len() < 4 {
+            continue;
+        }
+        let (code, rel) = record.split_at(3);
+        let mut code = code.chars();
+        let (index, worktree) = (code.next()?, code.next()?);
+        if index == 'R' || index == 'C' {
+            records.next(); // The original path of a rename or copy
+        }
+
+        let status = match (index, worktree) {
+            ('?', '?') => GitStatus::Untracked,
+            ('A' | 'R' | 'C', _) => GitStatus::Added,
+            (_, 'D') | ('D', _) => continue, // Gone from disk, so not in the tree
+            _ => GitStatus::Modified,
+        };
+
+        let Ok(rel_to_root) = toplevel.join(rel).strip_prefix(&canonical_root).map(Path::to_path_buf) else {
+            continue;
+        };
+        statuses.insert(root, root.join(rel_to_root), status);
+    }
+
+    Some(statuses)
+}
+
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git").arg("-C").arg(dir).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
@@ -0,0 +1,537 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::UNIX_EPOCH;
+
+use crate::frontmatter;
+use crate::title;
+
+const MAGIC: &[u8; 6] = b"RMDIDX";
+/// Bumped when the file layout changes, so older indexes are rebuilt
+const VERSION: u64 = 2;
+
+// BM25 parameters
+const K1: f32 = 1.2;
+const B: f32 = 0.75;
+
+/// How many times over a word in the title or a heading counts
+const TITLE_BOOST: u32 = 5;
+const HEADING_BOOST: u32 = 3;
+
+/// The index of an app's root, read from the cache by the first search that
+/// needs it, and whether it has to be checked against the files again
+#[derive(Clone)]
+pub struct SharedIndex {
+    index: Arc<Mutex<Option<Index>>>,
+    stale: Arc<AtomicBool>,
+}
+
+impl Default for SharedIndex {
+    fn default() -> Self {
+        Self {
+            index: Arc::default(),
+            stale: Arc::new(AtomicBool::new(true)),
+        }
+    }
+}
+
+impl SharedIndex {
+    /// Have the next search bring the index up to date, e.g. when the search
+    /// prompt opens or files changed
+    pub fn mark_stale(&self) {
+        self.stale.store(true, Ordering::Relaxed);
+    }
+
+    /// Lock the index, reading it from the cache the first time, and bring
+    /// it up to date with `files` if it was marked stale. `None` if cancelled.
+    pub fn lock_fresh(
+        &self,
+        root: &Path,
+        files: &[PathBuf],
+        cancelled: &AtomicBool,
+        progress: impl FnMut(usize, usize),
+    ) -> Option<MutexGuard<'_, Option<Index>>> {
+        let mut guard = self.index.lock().unwrap_or_else(PoisonError::into_inner);
+        let index = guard.get_or_insert_with(|| Index::open(root));
+        if self.stale.swap(false, Ordering::Relaxed) {
+            if !index.refresh(files, cancelled, progress) {
+                self.stale.store(true, Ordering::Relaxed);
+                return None;
+            }
+            // Searching still works if the cache can't be written
+            let _ = index.save();
+        }
+        Some(guard)
+    }
+}
+
+/// A document as it was when it was indexed
+#[derive(Debug, PartialEq)]
+struct Doc {
+    path: PathBuf,
+    modified: u64, // Nanoseconds since the epoch
+    size: u64,
+    length: u32, // Number of words
+    tags: Vec<String>,
+}
+
+/// Inverted index of the documents under one root, kept in the cache
+/// directory between runs and brought up to date by size and modification time
+#[derive(Default)]
+pub struct Index {
+    file: Option<PathBuf>,
+    docs: Vec<Option<Doc>>, // By id, `None` once removed
+    ids: HashMap<PathBuf, u32>,
+    postings: BTreeMap<String, Vec<(u32, u32)>>, // Word -> (doc id, boosted frequency), sorted by word
+    changed: bool,                               // Since it was read or saved
+}
+
+impl Index {
+    /// The stored index for `root`, or an empty one if there is none yet
+    pub fn open(root: &Path) -> Self {
+        let file = dirs::cache_dir().map(|dir| {
+            let root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+            let hash = fnv1a(root.as_os_str().as_encoded_bytes());
+            dir.join("rmd").join("index").join(format!("{:016x}.idx", hash))
+        });
+        let mut index = file.as_deref().and_then(|file| Self::read(file).ok()).unwrap_or_default();
+        index.file = file;
+        index
+    }
+
+    /// Index new and changed documents and drop the ones no longer listed.
+    /// Returns false if cancelled part-way; what was indexed so far is kept.
+    pub fn refresh(&mut self, files: &[PathBuf], cancelled: &AtomicBool, mut progress: impl FnMut(usize, usize)) -> bool {
+        let listed: HashSet<&Path> = files.iter().map(PathBuf::as_path).collect();
+        let gone: HashSet<u32> = self
+            .ids
+            .iter()
+            .filter(|(path, _)| !listed.contains(path.as_path()))
+            .map(|(_, &id)| id)
+            .collect();
+        self.remove(&gone);
+
+        let stale: Vec<(&PathBuf, u64, u64)> = files
+            .iter()
+            .filter_map(|path| {
+                let (modified, size) = stamp(path)?;
+                let doc = self.ids.get(path).and_then(|&id| self.docs[id as usize].as_ref());
+                let fresh = doc.is_some_and(|doc| doc.modified == modified && doc.size == size);
+                (!fresh).then_some((path, modified, size))
+            })
+            .collect();
+        let changed: HashSet<u32> = stale.iter().filter_map(|(path, _, _)| self.ids.get(*path).copied()).collect();
+        self.remove(&changed);
+
+        for (done, (path, modified, size)) in stale.iter().enumerate() {
+            if cancelled.load(Ordering::Relaxed) {
+                return false;
+            }
+            progress(done, stale.len());
+            // Unreadable files are indexed as empty, so they aren't retried every time
+            let content = fs::read_to_string(path).unwrap_or_default();
+            self.add(path, *modified, *size, &content);
+        }
+        true
+    }
+
+    /// Documents containing every term, best match first by BM25, among
+    /// those `keep` accepts given their path and tags. A term matches the
+    /// indexed words it is part of, so `conf` finds "config" and "reconfigure".
+    pub fn rank(&self, terms: &[String], keep: impl Fn(&Path, &[String]) -> bool) -> Vec<PathBuf> {
+        let live: Vec<&Doc> = self.docs.iter().flatten().collect();
+        let doc_count = live.len() as f32;
+        let avg_length = live.iter().map(|doc| doc.length as f32).sum::<f32>() / doc_count.max(1.0);
+
+        let terms: HashSet<&String> = terms.iter().collect();
+        let mut ranked: Vec<(u32, f32)> = if terms.is_empty() {
+            (0..self.docs.len() as u32).filter(|&id| self.docs[id as usize].is_some()).map(|id| (id, 0.0)).collect()
+        } else {
+            let mut scores: HashMap<u32, (f32, usize)> = HashMap::new(); // Score, terms matched
+            for term in &terms {
+                let mut frequencies: HashMap<u32, u32> = HashMap::new();
+                for postings in self.words_containing(term) {
+                    for &(id, frequency) in postings {
+                        *frequencies.entry(id).or_insert(0) += frequency;
+                    }
+                }
+
+                let df = frequencies.len() as f32;
+                let idf = ((doc_count - df + 0.5) / (df + 0.5) + 1.0).ln();
+                for (id, frequency) in frequencies {
+                    let Some(ref doc) = self.docs[id as usize] else { continue };
+                    let tf = frequency as f32;
+                    let norm = K1 * (1.0 - B + B * doc.length as f32 / avg_length.max(1.0));
+                    let entry = scores.entry(id).or_insert((0.0, 0));
+                    entry.0 += idf * tf * (K1 + 1.0) / (tf + norm);
+                    entry.1 += 1;
+                }
+            }
+            scores
+                .into_iter()
+                .filter(|(_, (_, matched))| *matched == terms.len())
+                .map(|(id, (score, _))| (id, score))
+                .collect()
+        };
+
+        let path = |id: u32| self.docs[id as usize].as_ref().map(|doc| doc.path.as_path());
+        ranked.retain(|&(id, _)| {
+            self.docs[id as usize]
+                .as_ref()
+                .is_some_and(|doc| keep(&doc.path, &doc.tags))
+        });
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| path(a.0).cmp(&path(b.0))));
+        ranked.into_iter().filter_map(|(id, _)| path(id).map(Path::to_path_buf)).collect()
+    }
+
+    /// The postings of every word containing `term`, the way a literal
+    /// search matches inside words. Only the word list is gone through,
+    /// which is much smaller than the documents.
+    fn words_containing<'a>(&'a self, term: &'a str) -> impl Iterator<Item = &'a Vec<(u32, u32)>> + 'a {
+        self.postings
+            .iter()
+            .filter(move |(word, _)| word.contains(term))
+            .map(|(_, postings)| postings)
+    }
+
+    /// Write the index to the cache directory, if anything changed
+    pub fn save(&mut self) -> io::Result<()> {
+        let Some(file) = self.file.clone() else {
+            return Ok(());
+        };
+        if !self.changed {
+            return Ok(());
+        }
+        self.compact();
+
+        // Write a temporary file first, so another rmd never reads half an index
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Named for this process, so two instances saving at once don't share it
+        let temp = file.with_extension(format!("{}.tmp", std::process::id()));
+        let mut w = BufWriter::new(File::create(&temp)?);
+        self.write_to(&mut w)?;
+        w.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&temp, &file)?;
+        self.changed = false;
+        Ok(())
+    }
+
+    fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        write_varint(w, VERSION)?;
+        // Postings refer to documents by position, so there must be no gaps
+        debug_assert!(self.docs.iter().all(Option::is_some), "compact before writing");
+        write_varint(w, self.docs.iter().flatten().count() as u64)?;
+        for doc in self.docs.iter().flatten() {
+            write_str(w, &doc.path.to_string_lossy())?;
+            write_varint(w, doc.modified)?;
+            write_varint(w, doc.size)?;
+            write_varint(w, doc.length as u64)?;
+            write_varint(w, doc.tags.len() as u64)?;
+            for tag in &doc.tags {
+                write_str(w, tag)?;
+            }
+        }
+        write_varint(w, self.postings.len() as u64)?;
+        for (word, postings) in &self.postings {
+            write_str(w, word)?;
+            write_varint(w, postings.len() as u64)?;
+            // Ids are ascending, so store the gaps between them
+            let mut previous = 0;
+            for &(id, frequency) in postings {
+                write_varint(w, (id - previous) as u64)?;
+                write_varint(w, frequency as u64)?;
+                previous = id;
+            }
+        }
+        Ok(())
+    }
+
+    fn read(file: &Path) -> io::Result<Self> {
+        Self::read_from(&mut BufReader::new(File::open(file)?))
+    }
+
+    fn read_from(r: &mut impl Read) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut magic = [0u8; 6];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC || read_varint(r)? != VERSION {
+            return Err(invalid("not an index of this version"));
+        }
+
+        let mut index = Self::default();
+        for id in 0..read_varint(r)? as u32 {
+            let path = PathBuf::from(read_str(r)?);
+            let modified = read_varint(r)?;
+            let size = read_varint(r)?;
+            let length = read_varint(r)? as u32;
+            let tags = (0..read_varint(r)?).map(|_| read_str(r)).collect::<io::Result<_>>()?;
+            index.ids.insert(path.clone(), id);
+            index.docs.push(Some(Doc { path, modified, size, length, tags }));
+        }
+        for _ in 0..read_varint(r)? {
+            let word = read_str(r)?;
+            // A word can't be in more documents than there are
+            let count = read_varint(r)?;
+            if count > index.docs.len() as u64 {
+                return Err(invalid("more postings than documents"));
+            }
+            let mut postings = Vec::with_capacity(count as usize);
+            let mut id: u32 = 0;
+            for _ in 0..count {
+                id = u32::try_from(read_varint(r)?)
+                    .ok()
+                    .and_then(|gap| id.checked_add(gap))
+                    .filter(|&id| (id as usize) < index.docs.len())
+                    .ok_or_else(|| invalid("posting for an unknown document"))?;
+                let frequency = read_varint(r)? as u32;
+                postings.push((id, frequency));
+            }
+            index.postings.insert(word, postings);
+        }
+        Ok(index)
+    }
+
+    fn add(&mut self, path: &Path, modified: u64, size: u64, content: &str) {
+        let id = self.docs.len() as u32;
+        let (frequencies, length) = word_frequencies(content);
+        for (word, frequency) in frequencies {
+            self.postings.entry(word).or_default().push((id, frequency));
+        }
+        let tags = frontmatter::parse(content)
+            .map(|fm| fm.tags.iter().map(|tag| tag.to_lowercase()).collect())
+            .unwrap_or_default();
+        self.docs.push(Some(Doc {
+            path: path.to_path_buf(),
+            modified,
+            size,
+            length,
+            tags,
+        }));
+        self.ids.insert(path.to_path_buf(), id);
+        self.changed = true;
+    }
+
+    fn remove(&mut self, ids: &HashSet<u32>) {
+        if ids.is_empty() {
+            return;
+        }
+        for &id in ids {
+            if let Some(doc) = self.docs[id as usize].take() {
+                self.ids.remove(&doc.path);
+            }
+        }
+        self.postings.retain(|_, postings| {
+            postings.retain(|(id, _)| !ids.contains(id));
+            !postings.is_empty()
+        });
+        self.changed = true;
+    }
+
+    /// Renumber the documents to close the gaps left by removed ones
+    fn compact(&mut self) {
+        let mut new_ids = vec![0u32; self.docs.len()];
+        let mut next = 0;
+        for (id, doc) in self.docs.iter().enumerate() {
+            if doc.is_some() {
+                new_ids[id] = next;
+                next += 1;
+            }
+        }
+        if next as usize == self.docs.len() {
+            return;
+        }
+        self.docs.retain(Option::is_some);
+        for id in self.ids.values_mut() {
+            *id = new_ids[*id as usize];
+        }
+        for postings in self.postings.values_mut() {
+            for (id, _) in postings.iter_mut() {
+                *id = new_ids[*id as usize];
+            }
+        }
+    }
+}
+
+/// Lowercased words, split at anything that isn't a letter, digit or `_`
+pub fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// How often each word occurs, with words in the title and headings counted
+/// several times over, and the number of words in the document
+fn word_frequencies(content: &str) -> (HashMap<String, u32>, u32) {
+    let mut frequencies: HashMap<String, u32> = HashMap::new();
+    let mut length = 0;
+    if let Some(title) = title::extract(content) {
+        for word in words(&title) {
+            *frequencies.entry(word).or_insert(0) += TITLE_BOOST - 1;
+        }
+    }
+
+    let mut in_fence = false;
+    for line in frontmatter::strip(content).lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        }
+        let weight = if !in_fence && trimmed.starts_with('#') { HEADING_BOOST } else { 1 };
+        for word in words(line) {
+            *frequencies.entry(word).or_insert(0) += weight;
+            length += 1;
+        }
+    }
+    (frequencies, length)
+}
+
+/// Modification time (in nanoseconds) and size, to tell whether a file changed
+fn stamp(path: &Path) -> Option<(u64, u64)> {
+    let meta = fs::metadata(path).ok()?;
+    let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_nanos() as u64;
+    Some((modified, meta.len()))
+}
+
+/// A hash that stays the same across runs and Rust versions, for file names
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| (hash ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+fn write_varint(w: &mut impl Write, mut n: u64) -> io::Result<()> {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            return w.write_all(&[byte]);
+        }
+        w.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint(r: &mut impl Read) -> io::Result<u64> {
+    let mut n = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0u8];
+        r.read_exact(&mut byte)?;
+        n |= ((byte[0] & 0x7f) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::InvalidData, "varint too long"))
+}
+
+fn write_str(w: &mut impl Write, s: &str) -> io::Result<()> {
+    write_varint(w, s.len() as u64)?;
+    w.write_all(s.as_bytes())
+}
+
+fn read_str(r: &mut impl Read) -> io::Result<String> {
+    let len = read_varint(r)? as usize;
+    let mut buf = Vec::new();
+    r.take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    String::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index_of(docs: &[(&str, &str)]) -> Index {
+        let mut index = Index::default();
+        for (modified, (path, content)) in docs.iter().enumerate() {
+            index.add(Path::new(path), modified as u64, content.len() as u64, content);
+        }
+        index
+    }
+
+    fn temp_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rmd-test-{}-{}.idx", std::process::id(), name))
+    }
+
+    fn ranked(index: &Index, terms: &[&str]) -> Vec<PathBuf> {
+        let terms: Vec<String> = terms.iter().map(|term| term.to_string()).collect();
+        index.rank(&terms, |_, _| true)
+    }
+
+    #[test]
+    fn save_and_read_round_trip() {
+        let file = temp_file("round-trip");
+        let mut index = index_of(&[
+            ("/docs/a.md", "---\ntags: [Ops, db]\n---\n# Deploy\n\nRoll out the config"),
+            ("/docs/b.md", "Nothing to see here, only the config"),
+        ]);
+        index.file = Some(file.clone());
+        index.save().unwrap();
+        let read = Index::read(&file).unwrap();
+        let _ = fs::remove_file(&file);
+
+        assert_eq!(read.docs, index.docs);
+        assert_eq!(read.ids, index.ids);
+        assert_eq!(read.postings, index.postings);
+        assert_eq!(read.docs[0].as_ref().unwrap().tags, ["ops", "db"]);
+    }
+
+    #[test]
+    fn postings_survive_remove_and_compact() {
+        let file = temp_file("compact");
+        let mut index = index_of(&[
+            ("/a.md", "shared alpha"),
+            ("/b.md", "shared beta"),
+            ("/c.md", "shared gamma"),
+        ]);
+        index.file = Some(file.clone());
+        index.remove(&HashSet::from([1]));
+        index.save().unwrap();
+        let read = Index::read(&file).unwrap();
+        let _ = fs::remove_file(&file);
+
+        assert_eq!(read.docs.len(), 2);
+        for (path, &id) in &read.ids {
+            assert_eq!(&read.docs[id as usize].as_ref().unwrap().path, path);
+        }
+        assert_eq!(ranked(&read, &["shared"]).len(), 2);
+        assert_eq!(ranked(&read, &["gamma"]), [PathBuf::from("/c.md")]);
+        assert!(ranked(&read, &["beta"]).is_empty());
+    }
+
+    #[test]
+    fn terms_match_inside_words() {
+        let index = index_of(&[("/a.md", "load the config"), ("/b.md", "reconfigure it")]);
+        let mut both = ranked(&index, &["conf"]);
+        both.sort();
+        assert_eq!(both, [PathBuf::from("/a.md"), PathBuf::from("/b.md")]);
+        assert_eq!(ranked(&index, &["conf", "load"]), [PathBuf::from("/a.md")]);
+        assert_eq!(ranked(&index, &["figure"]), [PathBuf::from("/b.md")]);
+        assert!(ranked(&index, &["conf", "missing"]).is_empty());
+    }
+
+    #[test]
+    fn corrupt_posting_count_is_rejected() {
+        let mut bytes = MAGIC.to_vec();
+        for n in [VERSION, 0, 1] {
+            write_varint(&mut bytes, n).unwrap();
+        }
+        write_str(&mut bytes, "word").unwrap();
+        write_varint(&mut bytes, u64::MAX).unwrap();
+        assert!(Index::read_from(&mut bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn posting_for_unknown_document_is_rejected() {
+        let mut index = index_of(&[("/a.md", "word")]);
+        index.postings.insert("word".to_string(), vec![(0, 1), (7, 1)]);
+        let mut bytes = Vec::new();
+        index.write_to(&mut bytes).unwrap();
+        assert!(Index::read_from(&mut bytes.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// A Normal mode motion that accepts a count prefix
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Motion {
+    Down,
+    Up,
+    Top,
+    Bottom,
+    HalfPageUp,
+    HalfPageDown,
+    PageUp,
+    PageDown,
+    NextHeading,
+    PreviousHeading,
+}
+
+/// Result of feeding a key into the sequence parser
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyInput {
+    /// The key was consumed as part of an unfinished sequence (`5`, `g`, `]`)
+    Pending,
+    /// A complete motion with its optional count (`5j`, `20G`, `3]]`)
+    Motion(Motion, Option<usize>),
+    /// `gt` (next tab, or tab N with a count) and `gT` (back N tabs)
+    NextTab(Option<usize>),
+    PreviousTab(Option<usize>),
+    /// Any other key, to be handled by the regular key bindings
+    Other(KeyEvent),
+}
+
+/// Parser for vim-style key sequences with numeric count prefixes
+#[derive(Debug, Default)]
+pub struct KeySequence {
+    count: Option<usize>,
+    prefix: Option<char>,
+}
+
+impl KeySequence {
+    /// Whether a count or multi-key prefix is waiting for more input
+    pub fn is_pending(&self) -> bool {
+        self.count.is_some() || self.prefix.is_some()
+    }
+
+    /// Discard any partially typed sequence
+    pub fn reset(&mut self) {
+        self.count = None;
+        self.prefix = None;
+    }
+
+    /// Text of the pending sequence, for display in the status bar
+    pub fn pending_text(&self) -> String {
+        let mut text = self.count.map(|c| c.to_string()).unwrap_or_default();
+        if let Some(p) = self.prefix {
+            text.push(p);
+        }
+        text
+    }
+
+    pub fn feed(&mut self, key: KeyEvent) -> KeyInput {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        // Second key of a two-key sequence
+        if let Some(prefix) = self.prefix.take() {
+            match (prefix, key.code) {
+                ('g', KeyCode::Char('t')) => return KeyInput::NextTab(self.count.take()),
+                ('g', KeyCode::Char('T')) => return KeyInput::PreviousTab(self.count.take()),
+                _ => {}
+            }
+            let motion = match (prefix, key.code) {
+                ('g', KeyCode::Char('g')) => Some(Motion::Top),
+                (']', KeyCode::Char(']')) => Some(Motion::NextHeading),
+                ('[', KeyCode::Char('[')) => Some(Motion::PreviousHeading),
+                _ => None,
+            };
+            return match motion {
+                Some(motion) => KeyInput::Motion(motion, self.count.take()),
+                None => {
+                    self.reset();
+                    KeyInput::Other(key)
+                }
+            };
+        }
+
+        // Count digits (a leading 0 is not a count)
+        if let KeyCode::Char(c @ '0'..='9') = key.code {
+            if !ctrl && (c != '0' || self.count.is_some()) {
+                let digit = c.to_digit(10).unwrap_or(0) as usize;
+                let count = self.count.unwrap_or(0).saturating_mul(10).saturating_add(digit);
+                self.count = Some(count);
+                return KeyInput::Pending;
+            }
+        }
+
+        let motion = match key.code {
+            KeyCode::Char(c @ ('g' | ']' | '[')) if !ctrl => {
+                self.prefix = Some(c);
+                return KeyInput::Pending;
+            }
+            KeyCode::Char('j') | KeyCode::Down if !ctrl => Some(Motion::Down),
+            KeyCode::Char('k') | KeyCode::Up if !ctrl => Some(Motion::Up),
+            KeyCode::Char('G') => Some(Motion::Bottom),
+            KeyCode::Char('u') if ctrl => Some(Motion::HalfPageUp),
+            KeyCode::Char('d') if ctrl => Some(Motion::HalfPageDown),
+            KeyCode::Char('b') if ctrl => Some(Motion::PageUp),
+            KeyCode::Char('f') if ctrl => Some(Motion::PageDown),
+            _ => None,
+        };
+
+        match motion {
+            Some(motion) => KeyInput::Motion(motion, self.count.take()),
+            None => {
+                self.reset();
+                KeyInput::Other(key)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    /// The input each sequence of keys ends in
+    fn feed(keys: &str) -> KeyInput {
+        let mut sequence = KeySequence::default();
+        let mut input = KeyInput::Pending;
+        for c in keys.chars() {
+            input = sequence.feed(key(c));
+        }
+        input
+    }
+
+    #[test]
+    fn counts_and_prefixes_make_motions() {
+        let cases = [
+            ("j", KeyInput::Motion(Motion::Down, None)),
+            ("5j", KeyInput::Motion(Motion::Down, Some(5))),
+            ("12k", KeyInput::Motion(Motion::Up, Some(12))),
+            ("gg", KeyInput::Motion(Motion::Top, None)),
+            ("20gg", KeyInput::Motion(Motion::Top, Some(20))),
+            ("20G", KeyInput::Motion(Motion::Bottom, Some(20))),
+            ("3]]", KeyInput::Motion(Motion::NextHeading, Some(3))),
+            ("[[", KeyInput::Motion(Motion::PreviousHeading, None)),
+            ("2gt", KeyInput::NextTab(Some(2))),
+            ("gT", KeyInput::PreviousTab(None)),
+            ("10", KeyInput::Pending),
+            ("g", KeyInput::Pending),
+            ("0", KeyInput::Other(key('0'))),
+            ("gx", KeyInput::Other(key('x'))),
+        ];
+        for (keys, expected) in cases {
+            assert_eq!(feed(keys), expected, "keys {:?}", keys);
+        }
+    }
+
+    #[test]
+    fn other_keys_reset_a_pending_sequence() {
+        let esc = KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE);
+        for pending in ["5", "5g", "]"] {
+            let mut sequence = KeySequence::default();
+            for c in pending.chars() {
+                sequence.feed(key(c));
+            }
+            assert_eq!(sequence.pending_text(), pending);
+            assert_eq!(sequence.feed(esc), KeyInput::Other(esc));
+            assert!(!sequence.is_pending());
+            assert_eq!(sequence.feed(key('j')), KeyInput::Motion(Motion::Down, None));
+        }
+    }
+}
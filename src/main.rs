@@ -1,14 +1,31 @@
 mod app;
+mod check;
+mod command;
+mod editor;
 mod file_tree;
+mod fileops;
+mod filetype;
+mod frontmatter;
+mod git;
+mod index;
+mod keys;
 mod markdown;
+mod scanner;
+mod search;
+mod stdin;
+mod tabs;
+mod title;
 mod ui;
+mod util;
+mod watcher;
 
-use std::io;
-use std::path::PathBuf;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers, MouseEventKind},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -17,37 +34,52 @@ use ratatui::{
     Terminal,
 };
 
-use app::{App, AppMode};
+use app::{App, AppMode, Focus, Settings};
+use check::CheckOptions;
+use keys::KeyInput;
+use tabs::Tabs;
 
 #[derive(Parser, Debug)]
 #[command(name = "rmd")]
 #[command(about = "A terminal-based Markdown document viewer")]
 #[command(version)]
 struct Args {
-    /// Directory to browse for Markdown files (defaults to current directory)
-    #[arg(default_value = ".")]
-    path: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Directories or files to open, each in its own tab; `-` reads Markdown from stdin
+    /// (defaults to the current directory, or stdin when it is piped). Open a directory
+    /// named `check` with `rmd -- check` or `rmd ./check`
+    paths: Vec<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check Markdown files for broken links, anchors and images
+    Check {
+        /// Directory to check (defaults to current directory)
+        #[arg(default_value = ".")]
+        path: PathBuf,
+
+        /// Also check external http(s) URLs
+        #[arg(long)]
+        external: bool,
+    },
 }
 
 fn main() -> io::Result<()> {
     let args = Args::parse();
 
-    // Resolve the path
-    let path = if args.path.is_absolute() {
-        args.path
-    } else {
-        std::env::current_dir()?.join(args.path)
-    };
-
-    if !path.exists() {
-        eprintln!("Error: Path '{}' does not exist", path.display());
-        std::process::exit(1);
+    if let Some(Command::Check { path, external }) = args.command {
+        let path = resolve_dir(path)?;
+        let options = CheckOptions {
+            external,
+            file_types: Settings::load().file_types,
+        };
+        run_check(&path, &options);
     }
 
-    if !path.is_dir() {
-        eprintln!("Error: '{}' is not a directory", path.display());
-        std::process::exit(1);
-    }
+    let apps = open_paths(args.paths)?;
 
     // Setup terminal
     enable_raw_mode()?;
@@ -56,9 +88,9 @@ fn main() -> io::Result<()> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    // Create app and run
-    let mut app = App::new(path);
-    let res = run_app(&mut terminal, &mut app);
+    // Run one app per path
+    let mut tabs = Tabs::new(apps);
+    let res = run_app(&mut terminal, &mut tabs);
 
     // Restore terminal
     disable_raw_mode()?;
@@ -77,12 +109,137 @@ fn main() -> io::Result<()> {
     Ok(())
 }
 
+/// Create an app for each path argument: a directory is browsed, a file is
+/// opened with its directory as the root, and `-` reads stdin
+fn open_paths(mut paths: Vec<PathBuf>) -> io::Result<Vec<App>> {
+    if paths.is_empty() {
+        let piped = !io::stdin().is_terminal();
+        paths.push(PathBuf::from(if piped { "-" } else { "." }));
+    }
+    if paths.iter().filter(|p| p.as_os_str() == "-").count() > 1 {
+        eprintln!("Error: '-' (stdin) can only be given once");
+        std::process::exit(1);
+    }
+
+    let mut apps = Vec::new();
+    for path in paths {
+        if path.as_os_str() == "-" {
+            apps.push(App::from_stdin(std::env::current_dir()?, stdin::read_in_background()));
+            continue;
+        }
+        let path = resolve_path(path)?;
+        if path.is_dir() {
+            apps.push(App::new(path));
+        } else {
+            let root = path.parent().map(Path::to_path_buf).unwrap_or_else(|| path.clone());
+            let mut app = App::new(root);
+            app.open_path(&path.to_string_lossy());
+            apps.push(app);
+        }
+    }
+    Ok(apps)
+}
+
+/// Resolve a path argument to an absolute path, exiting if it does not exist
+fn resolve_path(path: PathBuf) -> io::Result<PathBuf> {
+    let path = if path.is_absolute() {
+        path
+    } else {
+        std::env::current_dir()?.join(path)
+    };
+
+    if !path.exists() {
+        eprintln!("Error: Path '{}' does not exist", path.display());
+        std::process::exit(1);
+    }
+
+    Ok(path)
+}
+
+/// Resolve a directory argument to an absolute path, exiting if it is unusable
+fn resolve_dir(path: PathBuf) -> io::Result<PathBuf> {
+    let path = resolve_path(path)?;
+
+    if !path.is_dir() {
+        eprintln!("Error: '{}' is not a directory", path.display());
+        std::process::exit(1);
+    }
+
+    Ok(path)
+}
+
+/// Run the non-interactive link checker and exit with its status
+fn run_check(root: &Path, options: &CheckOptions) -> ! {
+    let diagnostics = check::check_links(root, options);
+    let cwd = std::env::current_dir().unwrap_or_else(|_| root.to_path_buf());
+
+    for diag in &diagnostics {
+        let display_path = diag.path.strip_prefix(&cwd).unwrap_or(&diag.path);
+        println!("{}:{}: {}", display_path.display(), diag.line, diag.message);
+    }
+
+    if diagnostics.is_empty() {
+        std::process::exit(0);
+    }
+
+    let mut files: Vec<_> = diagnostics.iter().map(|d| &d.path).collect();
+    files.dedup();
+    eprintln!(
+        "{} broken link(s) in {} file(s)",
+        diagnostics.len(),
+        files.len()
+    );
+    std::process::exit(1);
+}
+
+/// Suspend the TUI, run the editor on the open document and restore the terminal
+fn open_in_editor<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
+    let Some((path, line)) = app.edit_target() else {
+        app.status_message = Some("No document open".to_string());
+        return Ok(());
+    };
+    let Some(mut command) = editor::command(&path, line, &app.settings.editor_line_args) else {
+        app.status_message = Some("Set $VISUAL or $EDITOR to edit documents".to_string());
+        return Ok(());
+    };
+
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture)?;
+    let status = command.status();
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+    terminal.clear()?;
+
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => app.status_message = Some(format!("Editor exited with {}", status)),
+        Err(e) => app.status_message = Some(format!("Could not run editor: {}", e)),
+    }
+    app.reload_current_file();
+    Ok(())
+}
+
 fn run_app<B: ratatui::backend::Backend>(
     terminal: &mut Terminal<B>,
-    app: &mut App,
+    tabs: &mut Tabs,
 ) -> io::Result<()> {
+    let mut needs_draw = true;
+
     loop {
-        terminal.draw(|f| ui::draw(f, app))?;
+        // Pick up edits made on disk, scan progress, git status and stdin while we wait for input
+        if tabs.process_background() || needs_draw {
+            terminal.draw(|f| ui::draw(f, tabs))?;
+            needs_draw = false;
+        }
+
+        // Poll faster while a scan is filling in a tree, a search is running or stdin is streaming in
+        let timeout = if tabs.is_busy() { 50 } else { 250 };
+        if !event::poll(Duration::from_millis(timeout))? {
+            continue;
+        }
+        needs_draw = true;
+
+        let app = &mut tabs.apps[tabs.active];
 
         match event::read()? {
             Event::Key(key) => {
@@ -92,9 +249,19 @@ fn run_app<B: ratatui::backend::Backend>(
                         match key.code {
                             KeyCode::Esc => app.exit_search_mode(),
                             KeyCode::Enter => app.search_select(),
-                            KeyCode::Up => app.search_previous(),
-                            KeyCode::Down => app.search_next(),
+                            KeyCode::Up => app.search_up(),
+                            KeyCode::Down => app.search_down(),
                             KeyCode::Backspace => app.search_backspace(),
+                            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                app.toggle_search_regex();
+                            }
+                            KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                app.toggle_search_fuzzy();
+                            }
+                            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::ALT) => app.cycle_search_case(),
+                            KeyCode::Char('w') if key.modifiers.contains(KeyModifiers::ALT) => {
+                                app.toggle_search_whole_word();
+                            }
                             KeyCode::Char(c) => app.search_add_char(c),
                             _ => {}
                         }
@@ -124,6 +291,8 @@ fn run_app<B: ratatui::backend::Backend>(
                                 app.doc_search_next();
                             }
                             KeyCode::Backspace => app.doc_search_backspace(),
+                            KeyCode::Up => app.history_previous(),
+                            KeyCode::Down => app.history_next(),
                             KeyCode::Char('n') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                                 // Ctrl+n: next match (vim style in search)
                                 app.doc_search_next();
@@ -132,6 +301,18 @@ fn run_app<B: ratatui::backend::Backend>(
                                 // Ctrl+p: previous match (vim style in search)
                                 app.doc_search_previous();
                             }
+                            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                // Ctrl+r: toggle regular expressions
+                                app.toggle_search_regex();
+                            }
+                            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::ALT) => {
+                                // Alt+c: ignore case / smart case / match case
+                                app.cycle_search_case();
+                            }
+                            KeyCode::Char('w') if key.modifiers.contains(KeyModifiers::ALT) => {
+                                // Alt+w: whole words only
+                                app.toggle_search_whole_word();
+                            }
                             KeyCode::Char(c) => app.doc_search_add_char(c),
                             _ => {}
                         }
@@ -144,12 +325,84 @@ fn run_app<B: ratatui::backend::Backend>(
                             execute!(io::stdout(), EnableMouseCapture)?;
                         }
                     }
+                    AppMode::Command => {
+                        // Command line key handling
+                        match key.code {
+                            KeyCode::Esc => app.exit_command_mode(),
+                            KeyCode::Enter => app.command_execute(),
+                            KeyCode::Tab => app.command_complete(false),
+                            KeyCode::BackTab => app.command_complete(true),
+                            KeyCode::Backspace => app.command_backspace(),
+                            KeyCode::Char(c) => app.command_add_char(c),
+                            _ => {}
+                        }
+                    }
+                    AppMode::FileOp => {
+                        // Sidebar file operation prompt
+                        let confirmation = app.file_op.as_ref().is_some_and(|op| op.is_confirmation());
+                        match key.code {
+                            KeyCode::Char('y') | KeyCode::Char('Y') if confirmation => app.confirm_file_op(),
+                            _ if confirmation => app.cancel_file_op(),
+                            KeyCode::Esc => app.cancel_file_op(),
+                            KeyCode::Enter => app.confirm_file_op(),
+                            KeyCode::Tab => app.file_op_next_template(),
+                            KeyCode::Backspace => app.file_op_backspace(),
+                            KeyCode::Char(c) => app.file_op_add_char(c),
+                            _ => {}
+                        }
+                    }
+                    AppMode::Filter => {
+                        // Sidebar filter key handling
+                        match key.code {
+                            KeyCode::Esc => app.clear_filter(),
+                            KeyCode::Enter => app.accept_filter(),
+                            KeyCode::Up => app.previous(),
+                            KeyCode::Down => app.next(),
+                            KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => app.previous(),
+                            KeyCode::Char('n') if key.modifiers.contains(KeyModifiers::CONTROL) => app.next(),
+                            KeyCode::Backspace => app.filter_backspace(),
+                            KeyCode::Char(c) => app.filter_add_char(c),
+                            _ => {}
+                        }
+                    }
                     AppMode::Normal => {
-                        // Reset pending_g if any other key is pressed (except 'g' itself)
-                        if key.code != KeyCode::Char('g') && app.pending_g {
-                            app.pending_g = false;
+                        app.status_message = None;
+
+                        // Esc cancels a pending count or sequence instead of quitting
+                        if key.code == KeyCode::Esc && app.keys.is_pending() {
+                            app.keys.reset();
+                            continue;
                         }
 
+                        // Esc clears an active sidebar filter instead of quitting
+                        if key.code == KeyCode::Esc && !app.sidebar_filter.is_empty() {
+                            app.clear_filter();
+                            continue;
+                        }
+
+                        // ... and then a document search kept for `n` / `N`
+                        if key.code == KeyCode::Esc && app.has_doc_search() {
+                            app.clear_doc_search();
+                            continue;
+                        }
+
+                        let key = match app.keys.feed(key) {
+                            KeyInput::Pending => continue,
+                            KeyInput::Motion(motion, count) => {
+                                app.apply_motion(motion, count);
+                                continue;
+                            }
+                            KeyInput::NextTab(count) => {
+                                tabs.select_next(count);
+                                continue;
+                            }
+                            KeyInput::PreviousTab(count) => {
+                                tabs.select_previous(count);
+                                continue;
+                            }
+                            KeyInput::Other(key) => key,
+                        };
+
                         // Quit on q, Esc, or Ctrl+c
                         if key.code == KeyCode::Char('q')
                             || key.code == KeyCode::Esc
@@ -159,54 +412,50 @@ fn run_app<B: ratatui::backend::Backend>(
                         }
 
                         match key.code {
-                            // Navigation
-                            KeyCode::Char('j') | KeyCode::Down => app.next(),
-                            KeyCode::Char('k') | KeyCode::Up => app.previous(),
+                            // Navigation (j/k, gg/G, ]]/[[ and the Ctrl page keys are
+                            // count-aware motions handled by the key sequence parser)
                             KeyCode::Char('l') | KeyCode::Right => app.focus_content_or_select(),
                             KeyCode::Char('h') | KeyCode::Left => app.focus_sidebar_or_collapse(),
                             KeyCode::Enter => app.toggle_or_select(),
                             KeyCode::Tab => app.toggle_focus(),
 
-                            // gg for top, G for bottom
-                            KeyCode::Char('g') => {
-                                if app.pending_g {
-                                    app.scroll_to_top();
-                                    app.pending_g = false;
-                                } else {
-                                    app.pending_g = true;
-                                }
-                            }
-                            KeyCode::Char('G') => app.scroll_to_bottom(),
+                            // Sidebar visibility and width
+                            KeyCode::Char('b') => app.toggle_sidebar(),
+                            KeyCode::Char('<') => app.resize_sidebar(-5),
+                            KeyCode::Char('>') => app.resize_sidebar(5),
 
-                            // Vim-style page navigation
-                            KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                                // Ctrl+u: half page up
-                                let half_page = (app.content_height / 2) as usize;
-                                app.content_scroll = app.content_scroll.saturating_sub(half_page);
-                            }
-                            KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                                // Ctrl+d: half page down
-                                let half_page = (app.content_height / 2) as usize;
-                                let max_scroll = app.total_wrapped_lines().saturating_sub(app.content_height as usize);
-                                app.content_scroll = (app.content_scroll + half_page).min(max_scroll);
-                            }
-                            KeyCode::Char('b') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                                // Ctrl+b: full page up
-                                let page_size = app.content_height as usize;
-                                app.content_scroll = app.content_scroll.saturating_sub(page_size);
-                            }
-                            KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                                // Ctrl+f: full page down
-                                let page_size = app.content_height as usize;
-                                let max_scroll = app.total_wrapped_lines().saturating_sub(app.content_height as usize);
-                                app.content_scroll = (app.content_scroll + page_size).min(max_scroll);
+                            // Show hidden and ignored entries
+                            KeyCode::Char('.') => app.toggle_show_ignored(),
+
+                            // Filter the sidebar
+                            KeyCode::Char('f') => app.enter_filter_mode(),
+
+                            // Edit the open document at the visible line
+                            KeyCode::Char('e') => {
+                                open_in_editor(terminal, app)?;
                             }
 
+                            // Cycle sidebar sort order
+                            KeyCode::Char('o') => app.cycle_sort_mode(),
+                            KeyCode::Char('t') => app.toggle_sidebar_label(),
+
+                            // File operations on the sidebar selection
+                            KeyCode::Char('a') if app.focus == Focus::Sidebar => app.begin_new_file(false),
+                            KeyCode::Char('A') if app.focus == Focus::Sidebar => app.begin_new_file(true),
+                            KeyCode::Char('r') if app.focus == Focus::Sidebar => app.begin_rename(),
+                            KeyCode::Char('m') if app.focus == Focus::Sidebar => app.begin_move(),
+                            KeyCode::Char('d') if app.focus == Focus::Sidebar => app.begin_delete(),
+
                             // Search
                             KeyCode::Char('/') => {
                                 // /: search in document (vim style)
                                 app.enter_doc_search_mode();
                             }
+                            KeyCode::Char('n') => app.doc_search_next(),
+                            KeyCode::Char('N') => app.doc_search_previous(),
+
+                            // Command line (vim style)
+                            KeyCode::Char(':') => app.enter_command_mode(),
 
                             // Global search (custom, no vim equivalent)
                             KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
@@ -239,21 +488,12 @@ fn run_app<B: ratatui::backend::Backend>(
                         }
                     }
                 }
-            }
-            Event::Mouse(mouse) => {
-                match mouse.kind {
-                    MouseEventKind::ScrollUp => {
-                        // Scroll content up 3 lines
-                        app.content_scroll = app.content_scroll.saturating_sub(3);
-                    }
-                    MouseEventKind::ScrollDown => {
-                        // Scroll content down 3 lines
-                        let max_scroll = app.total_wrapped_lines().saturating_sub(app.content_height as usize);
-                        app.content_scroll = (app.content_scroll + 3).min(max_scroll);
-                    }
-                    _ => {}
+
+                if app.should_quit {
+                    return Ok(());
                 }
             }
+            Event::Mouse(mouse) => app.handle_mouse(mouse),
             _ => {}
         }
     }
@@ -17,11 +17,14 @@ pub struct RenderedLine {
     pub segments: Vec<TextSegment>,
     pub heading_level: u8,
     pub is_code_block: bool,
+    pub is_code_fence: bool,           // The ``` lines around a code block
+    pub code_language: Option<String>, // Of a fenced code block, from its info string
     pub is_blockquote: bool,
     pub is_list_item: bool,
     pub is_horizontal_rule: bool,
     pub is_table_row: bool,
     pub is_table_separator: bool,
+    pub source_line: usize, // 0-based line in the source file this line came from
 }
 
 impl RenderedLine {
@@ -30,11 +33,14 @@ impl RenderedLine {
             segments: Vec::new(),
             heading_level: 0,
             is_code_block: false,
+            is_code_fence: false,
+            code_language: None,
             is_blockquote: false,
             is_list_item: false,
             is_horizontal_rule: false,
             is_table_row: false,
             is_table_separator: false,
+            source_line: 0,
         }
     }
 
@@ -60,7 +66,6 @@ impl RenderedLine {
         self.segments.push(TextSegment::Strong(text));
     }
 
-    #[allow(dead_code)]
     pub fn to_plain_string(&self) -> String {
         self.segments
             .iter()
@@ -79,12 +84,13 @@ impl RenderedLine {
 pub fn render_markdown(content: &str) -> Vec<RenderedLine> {
     let mut options = Options::empty();
     options.insert(Options::ENABLE_TABLES);
-    let parser = Parser::new_ext(content, options);
+    let parser = Parser::new_ext(content, options).into_offset_iter();
     let mut lines: Vec<RenderedLine> = Vec::new();
     let mut current_line = RenderedLine::new();
     let mut current_text = String::new();
 
     let mut in_code_block = false;
+    let mut code_language: Option<String> = None;
     let mut list_depth: usize = 0;
     let mut ordered_list_index: Vec<u64> = Vec::new();
     let mut in_blockquote = false;
@@ -104,7 +110,20 @@ pub fn render_markdown(content: &str) -> Vec<RenderedLine> {
     let mut table_rows: Vec<Vec<String>> = Vec::new();
     let mut is_first_row = false;
 
-    for event in parser {
+    // Source line of each rendered line, for jumping to the same spot in an editor
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset).saturating_sub(1);
+    let mut pending_source: Option<usize> = None; // Where the line being built started
+
+    for (event, range) in parser {
+        let event_line = line_of(range.start);
+        let first_new_line = lines.len();
+        let line_start = *pending_source.get_or_insert(event_line);
+        // Code and quote text arrives as one event spanning several source lines
+        let per_line = matches!(event, Event::Text(_)) && !in_table && (in_code_block || in_blockquote);
+
         match event {
             Event::Start(tag) => {
                 match tag {
@@ -151,8 +170,16 @@ pub fn render_markdown(content: &str) -> Vec<RenderedLine> {
                             CodeBlockKind::Fenced(lang) => lang.to_string(),
                             CodeBlockKind::Indented => String::new(),
                         };
+                        // The info string may carry attributes, e.g. "rust,ignore"
+                        code_language = lang
+                            .split(|c: char| c == ',' || c == '{' || c.is_whitespace())
+                            .next()
+                            .filter(|name| !name.is_empty())
+                            .map(str::to_lowercase);
                         let mut marker_line = RenderedLine::new();
                         marker_line.is_code_block = true;
+                        marker_line.is_code_fence = true;
+                        marker_line.code_language = code_language.clone();
                         if lang.is_empty() {
                             marker_line.push_plain("```".to_string());
                         } else {
@@ -289,6 +316,8 @@ pub fn render_markdown(content: &str) -> Vec<RenderedLine> {
                         in_code_block = false;
                         let mut marker_line = RenderedLine::new();
                         marker_line.is_code_block = true;
+                        marker_line.is_code_fence = true;
+                        marker_line.code_language = code_language.take();
                         marker_line.push_plain("```".to_string());
                         lines.push(marker_line);
                         lines.push(RenderedLine::new());
@@ -434,6 +463,7 @@ pub fn render_markdown(content: &str) -> Vec<RenderedLine> {
                     for line in text.lines() {
                         let mut code_line = RenderedLine::new();
                         code_line.is_code_block = true;
+                        code_line.code_language = code_language.clone();
                         code_line.push_plain(format!("  {}", line));
                         lines.push(code_line);
                     }
@@ -499,12 +529,20 @@ pub fn render_markdown(content: &str) -> Vec<RenderedLine> {
             }
             _ => {}
         }
+
+        if lines.len() > first_new_line {
+            for (idx, line) in lines[first_new_line..].iter_mut().enumerate() {
+                line.source_line = if per_line { event_line + idx } else { line_start };
+            }
+            pending_source = None;
+        }
     }
 
     if !current_text.is_empty() {
         current_line.push_plain(current_text);
     }
     if !current_line.segments.is_empty() {
+        current_line.source_line = pending_source.unwrap_or(0);
         lines.push(current_line);
     }
 
@@ -521,3 +559,76 @@ pub fn render_markdown(content: &str) -> Vec<RenderedLine> {
 
     lines
 }
+
+/// Render plain text verbatim, one line per source line
+pub fn render_plain_text(content: &str) -> Vec<RenderedLine> {
+    let mut lines: Vec<RenderedLine> = content
+        .lines()
+        .enumerate()
+        .map(|(idx, text)| {
+            let mut line = RenderedLine::new();
+            line.push_plain(text.replace('\t', "    "));
+            line.source_line = idx;
+            line
+        })
+        .collect();
+
+    if lines.is_empty() {
+        let mut empty_line = RenderedLine::new();
+        empty_line.push_plain("(Empty file)".to_string());
+        lines.push(empty_line);
+    }
+
+    lines
+}
+
+/// Convert heading text to a GitHub-style anchor slug
+pub fn heading_slug(text: &str) -> String {
+    text.trim()
+        .chars()
+        .filter_map(|c| {
+            if c.is_alphanumeric() || c == '_' || c == '-' {
+                Some(c.to_lowercase().collect::<String>())
+            } else if c == ' ' {
+                Some("-".to_string())
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Collect the anchor slugs of all headings in a document, numbering duplicates
+/// the way GitHub does (`intro`, `intro-1`, ...)
+pub fn heading_slugs(content: &str) -> Vec<String> {
+    let parser = Parser::new_ext(content, Options::ENABLE_TABLES);
+    let mut slugs: Vec<String> = Vec::new();
+    let mut in_heading = false;
+    let mut heading_text = String::new();
+
+    for event in parser {
+        match event {
+            Event::Start(Tag::Heading { .. }) => {
+                in_heading = true;
+                heading_text.clear();
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                in_heading = false;
+                let base = heading_slug(&heading_text);
+                let mut slug = base.clone();
+                let mut n = 1;
+                while slugs.contains(&slug) {
+                    slug = format!("{}-{}", base, n);
+                    n += 1;
+                }
+                slugs.push(slug);
+            }
+            Event::Text(text) | Event::Code(text) if in_heading => {
+                heading_text.push_str(&text);
+            }
+            _ => {}
+        }
+    }
+
+    slugs
+}
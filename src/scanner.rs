@@ -0,0 +1,241 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+use ignore::WalkBuilder;
+use walkdir::WalkDir;
+
+use crate::file_tree::{describe_io_error, file_meta, NodeMeta, SortMode, TreeOptions, IGNORE_FILENAME};
+
+/// A walked entry (path and whether it is a directory), or a path that failed and why
+type WalkItem = Result<(PathBuf, bool), (PathBuf, String)>;
+
+/// Progress reported by the background scan
+#[derive(Debug)]
+pub enum ScanEvent {
+    /// A document was found, with its metadata
+    Document(PathBuf, NodeMeta),
+    /// A directory's subtree is complete; `None` means it holds no documents
+    Dir(PathBuf, Option<NodeMeta>),
+    /// An entry that could not be read, with the reason
+    Error(PathBuf, String),
+    /// The whole tree has been scanned
+    Finished,
+}
+
+/// Walks the whole tree once in a background thread, so the sidebar can
+/// show directories before knowing whether they contain any documents
+pub struct Scanner {
+    rx: Receiver<ScanEvent>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Scanner {
+    pub fn start(root: &Path, options: TreeOptions) -> Self {
+        let (tx, rx) = channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&cancelled);
+        let root = root.to_path_buf();
+
+        thread::spawn(move || scan(&root, &options, &tx, &flag));
+
+        Self { rx, cancelled }
+    }
+
+    /// Drain all pending events without blocking
+    pub fn poll_events(&self) -> Vec<ScanEvent> {
+        self.rx.try_iter().collect()
+    }
+}
+
+/// Walk one subtree on the calling thread, e.g. a directory that was just
+/// created, returning what a background scan of it would report (without
+/// `Finished`)
+pub fn scan_now(dir: &Path, options: &TreeOptions) -> Vec<ScanEvent> {
+    let (tx, rx) = channel();
+    scan(dir, options, &tx, &AtomicBool::new(false));
+    drop(tx);
+    rx.into_iter().filter(|event| !matches!(event, ScanEvent::Finished)).collect()
+}
+
+impl Drop for Scanner {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// A directory whose entries are still being walked
+struct OpenDir {
+    path: PathBuf,
+    meta: NodeMeta,
+    has_documents: bool,
+}
+
+impl OpenDir {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            meta: NodeMeta::default(),
+            has_documents: false,
+        }
+    }
+
+    /// Fold a document or finished subdirectory into this directory's totals
+    fn add(&mut self, meta: &NodeMeta) {
+        self.has_documents = true;
+        self.meta.add(meta);
+    }
+}
+
+fn scan(root: &Path, options: &TreeOptions, tx: &Sender<ScanEvent>, cancelled: &AtomicBool) {
+    // Depth-first, so a directory is complete once the walk leaves it.
+    // Both walkers follow symlinks and report loops as errors instead of descending.
+    let entries: Box<dyn Iterator<Item = WalkItem>> = if options.show_ignored {
+        Box::new(
+            WalkDir::new(root)
+                .min_depth(1)
+                .follow_links(true)
+                .sort_by_file_name()
+                .into_iter()
+                .filter_map(|entry| match entry {
+                    Ok(e) => {
+                        let is_dir = e.file_type().is_dir();
+                        Some(Ok((e.into_path(), is_dir)))
+                    }
+                    Err(e) => walkdir_error(&e).map(Err),
+                }),
+        )
+    } else {
+        Box::new(
+            WalkBuilder::new(root)
+                .follow_links(true)
+                .require_git(false)
+                .add_custom_ignore_filename(IGNORE_FILENAME)
+                .sort_by_file_name(|a, b| a.cmp(b))
+                .build()
+                .filter_map(|entry| match entry {
+                    Ok(e) if e.depth() == 0 => None,
+                    Ok(e) => {
+                        let is_dir = e.file_type().map(|t| t.is_dir()).unwrap_or(false);
+                        Some(Ok((e.into_path(), is_dir)))
+                    }
+                    Err(e) => ignore_error(&e).map(Err),
+                }),
+        )
+    };
+
+    let load_contents = options.load_contents || options.sort_mode == SortMode::FrontMatter;
+    let mut stack = vec![OpenDir::new(root.to_path_buf())];
+
+    for entry in entries {
+        if cancelled.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let path = match entry {
+            Ok((ref path, _)) => path.clone(),
+            Err((ref path, _)) => path.clone(),
+        };
+        // An error about an open directory (e.g. it can't be listed) belongs to that directory
+        let is_open_dir = entry.is_err() && stack.iter().any(|dir| dir.path == path);
+        while stack.len() > 1
+            && !(is_open_dir && stack[stack.len() - 1].path == path)
+            && path.parent() != Some(stack[stack.len() - 1].path.as_path())
+        {
+            if !finish_dir(&mut stack, tx) {
+                return;
+            }
+        }
+
+        let (path, is_dir) = match entry {
+            Ok(entry) => entry,
+            Err((path, message)) => {
+                if !worth_reporting(&path, options) {
+                    continue;
+                }
+                // Keep the entry (and its ancestors) so the error can be shown
+                if let Some(dir) = stack.last_mut() {
+                    dir.add(&NodeMeta::default());
+                }
+                if tx.send(ScanEvent::Error(path, message)).is_err() {
+                    return;
+                }
+                continue;
+            }
+        };
+
+        if is_dir {
+            stack.push(OpenDir::new(path));
+        } else if options.file_types.is_document(&path) {
+            let meta = file_meta(&path, load_contents);
+            if let Some(dir) = stack.last_mut() {
+                dir.add(&meta);
+            }
+            if tx.send(ScanEvent::Document(path, meta)).is_err() {
+                return;
+            }
+        }
+    }
+
+    while !stack.is_empty() {
+        if !finish_dir(&mut stack, tx) {
+            return;
+        }
+    }
+    let _ = tx.send(ScanEvent::Finished);
+}
+
+/// Report the innermost open directory and fold it into its parent.
+/// Returns false if the receiving side has gone away.
+fn finish_dir(stack: &mut Vec<OpenDir>, tx: &Sender<ScanEvent>) -> bool {
+    let Some(dir) = stack.pop() else {
+        return true;
+    };
+    if dir.has_documents {
+        if let Some(parent) = stack.last_mut() {
+            parent.add(&dir.meta);
+        }
+    }
+    let meta = if dir.has_documents { Some(dir.meta) } else { None };
+    tx.send(ScanEvent::Dir(dir.path, meta)).is_ok()
+}
+
+/// Errors worth surfacing: unreadable directories, symlink loops and dangling
+/// links named like documents. Anything else would not have been shown anyway.
+fn worth_reporting(path: &Path, options: &TreeOptions) -> bool {
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_symlink() => path.is_dir() || options.file_types.is_document(path),
+        Ok(meta) => meta.is_dir(),
+        Err(_) => false,
+    }
+}
+
+fn walkdir_error(error: &walkdir::Error) -> Option<(PathBuf, String)> {
+    let path = error.path()?.to_path_buf();
+    let message = match (error.loop_ancestor(), error.io_error()) {
+        (Some(ancestor), _) => format!("symlink loop: points back to {}", ancestor.display()),
+        (None, Some(io)) => describe_io_error(io),
+        (None, None) => error.to_string(),
+    };
+    Some((path, message))
+}
+
+fn ignore_error(error: &ignore::Error) -> Option<(PathBuf, String)> {
+    match error {
+        ignore::Error::WithPath { path, err } => {
+            let message = match err.as_ref() {
+                ignore::Error::Io(io) => describe_io_error(io),
+                other => other.to_string(),
+            };
+            Some((path.clone(), message))
+        }
+        ignore::Error::Loop { ancestor, child } => Some((
+            child.clone(),
+            format!("symlink loop: points back to {}", ancestor.display()),
+        )),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => ignore_error(err),
+        _ => None,
+    }
+}
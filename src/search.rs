@@ -0,0 +1,1004 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, Thread};
+
+use globset::{GlobBuilder, GlobMatcher};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::filetype::{render_document, DocKind};
+use crate::index::{self, SharedIndex};
+use crate::markdown::RenderedLine;
+use crate::title;
+
+/// How a query's letter case is matched
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum CaseMode {
+    #[default]
+    Insensitive,
+    Smart, // Sensitive only when the query has capitals
+    Sensitive,
+}
+
+impl CaseMode {
+    pub fn display_name(self) -> &'static str {
+        match self {
+            CaseMode::Insensitive => "ignore case",
+            CaseMode::Smart => "smart case",
+            CaseMode::Sensitive => "match case",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            CaseMode::Insensitive => CaseMode::Smart,
+            CaseMode::Smart => CaseMode::Sensitive,
+            CaseMode::Sensitive => CaseMode::Insensitive,
+        }
+    }
+}
+
+/// Toggles shared by document and global search, saved in settings.json
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchOptions {
+    pub regex: bool,
+    pub fuzzy: bool, // Global search only; never on together with `regex`
+    pub case: CaseMode,
+    pub whole_word: bool,
+}
+
+impl SearchOptions {
+    /// The active options, for prompt titles. Document search has no fuzzy mode.
+    pub fn label(&self, global: bool) -> String {
+        let mut parts = vec![self.case.display_name()];
+        if self.regex {
+            parts.insert(0, "regex");
+        } else if self.fuzzy && global {
+            parts.insert(0, "fuzzy");
+        }
+        if self.whole_word {
+            parts.push("whole word");
+        }
+        parts.join(", ")
+    }
+}
+
+/// Most queries kept per prompt
+const HISTORY_LIMIT: usize = 100;
+
+/// Queries entered in the search prompts, oldest first, kept between runs
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchHistory {
+    pub global: Vec<String>,
+    pub document: Vec<String>,
+}
+
+impl SearchHistory {
+    fn path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("rmd").join("history.json"))
+    }
+
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Add a query to the global or document history, moving it to the end
+    /// if it is already there, and save. The file is read again first, so
+    /// other tabs and other instances don't drop each other's queries.
+    pub fn record(&mut self, global: bool, query: &str) {
+        if query.trim().is_empty() {
+            return;
+        }
+        *self = Self::load();
+        let queries = if global { &mut self.global } else { &mut self.document };
+        queries.retain(|q| q != query);
+        queries.push(query.to_string());
+        if queries.len() > HISTORY_LIMIT {
+            queries.drain(..queries.len() - HISTORY_LIMIT);
+        }
+
+        let Some(path) = Self::path() else { return };
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        if let Ok(content) = serde_json::to_string_pretty(self) {
+            let _ = fs::write(path, content);
+        }
+    }
+}
+
+/// Compile a search query: the query as a literal, or in regex mode as a
+/// regular expression
+pub fn compile(query: &str, options: &SearchOptions) -> Result<Regex, String> {
+    if !options.regex {
+        return Regex::new(&literal_pattern(regex::escape(query), query, options)).map_err(|e| describe(&e));
+    }
+    let pattern = if options.whole_word { format!(r"\b(?:{})\b", query) } else { query.to_string() };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(is_case_insensitive(query, options))
+        .build()
+        .map_err(|e| describe(&e))
+}
+
+/// Apply the whole-word and case options to the escaped pattern for literal
+/// `text`, as a group that can be combined with others
+fn literal_pattern(escaped: String, text: &str, options: &SearchOptions) -> String {
+    let mut pattern = escaped;
+    if options.whole_word {
+        // `\b` next to punctuation would require a word character beside it
+        let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+        let start = if is_word(text.chars().next()) { r"\b" } else { "" };
+        let end = if is_word(text.chars().last()) { r"\b" } else { "" };
+        pattern = format!("{}{}{}", start, pattern, end);
+    }
+    let flags = if is_case_insensitive(text, options) { "?i:" } else { "?:" };
+    format!("({}{})", flags, pattern)
+}
+
+fn is_case_insensitive(query: &str, options: &SearchOptions) -> bool {
+    match options.case {
+        CaseMode::Insensitive => true,
+        CaseMode::Smart => !has_uppercase(query, options.regex),
+        CaseMode::Sensitive => false,
+    }
+}
+
+/// Whether the query has capitals, not counting regex escapes like `\W`
+fn has_uppercase(query: &str, regex: bool) -> bool {
+    let mut escaped = false;
+    for c in query.chars() {
+        if escaped {
+            escaped = false;
+        } else if regex && c == '\\' {
+            escaped = true;
+        } else if c.is_uppercase() {
+            return true;
+        }
+    }
+    false
+}
+
+/// A global search query: words and "quoted phrases" that a document has to
+/// contain all of, `path:` and `tag:` filters, and `in:` and `lang:` scopes
+#[derive(Debug, Default, PartialEq)]
+pub struct Query {
+    pub parts: Vec<String>, // Words and phrases
+    pub paths: Vec<String>,
+    pub tags: Vec<String>,
+    pub blocks: Vec<String>,
+    pub languages: Vec<String>,
+}
+
+impl Query {
+    pub fn parse(text: &str) -> Self {
+        let mut query = Self::default();
+        let mut rest = text;
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() {
+                return query;
+            }
+            if let Some(quoted) = rest.strip_prefix('"') {
+                // An unterminated phrase runs to the end of the query
+                let end = quoted.find('"').unwrap_or(quoted.len());
+                if !quoted[..end].trim().is_empty() {
+                    query.parts.push(quoted[..end].to_string());
+                }
+                rest = quoted.get(end + 1..).unwrap_or_default();
+                continue;
+            }
+
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let word = &rest[..end];
+            rest = &rest[end..];
+            match word.split_once(':') {
+                Some(("path", glob)) if !glob.is_empty() => query.paths.push(glob.to_string()),
+                Some(("tag", tag)) if !tag.is_empty() => query.tags.push(tag.to_lowercase()),
+                Some(("in", block)) if !block.is_empty() => query.blocks.push(block.to_lowercase()),
+                Some(("lang", language)) if !language.is_empty() => query.languages.push(language.to_lowercase()),
+                _ => query.parts.push(word.to_string()),
+            }
+        }
+    }
+
+    /// Take the filters and scopes out of a regular expression, giving them
+    /// as a query along with the rest of the pattern as typed
+    pub fn parse_regex(text: &str) -> (Self, String) {
+        let mut query = Self::default();
+        let mut pattern = String::with_capacity(text.len());
+        let mut rest = text;
+        while !rest.is_empty() {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let word = &rest[..end];
+            let (list, value) = match word.split_once(':') {
+                Some(("path", glob)) if !glob.is_empty() => (&mut query.paths, glob.to_string()),
+                Some(("tag", tag)) if !tag.is_empty() => (&mut query.tags, tag.to_lowercase()),
+                Some(("in", block)) if !block.is_empty() => (&mut query.blocks, block.to_lowercase()),
+                Some(("lang", language)) if !language.is_empty() => (&mut query.languages, language.to_lowercase()),
+                _ => {
+                    let space = rest[end..].len() - rest[end..].trim_start().len();
+                    pattern.push_str(&rest[..end + space]);
+                    rest = &rest[end + space..];
+                    continue;
+                }
+            };
+            list.push(value);
+            rest = rest[end..].trim_start();
+        }
+        if query == Self::default() {
+            return (query, text.to_string());
+        }
+        (query, pattern.trim().to_string())
+    }
+}
+
+/// A kind of block an `in:` scope restricts the search to
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Block {
+    Code,
+    Heading,
+}
+
+/// Where in a document a scoped search looks: a line has to be in one of
+/// the `in:` blocks and fenced code in one of the `lang:` languages, if any
+/// are given. Lines are taken from the rendered document, so a `#` in a code
+/// block is not a heading.
+#[derive(Debug, Default)]
+struct Scopes {
+    blocks: Vec<Block>,
+    languages: Vec<String>,
+}
+
+impl Scopes {
+    fn new(query: &Query) -> Result<Self, String> {
+        let blocks = query
+            .blocks
+            .iter()
+            .map(|name| match name.as_str() {
+                "code" => Ok(Block::Code),
+                "heading" | "headings" => Ok(Block::Heading),
+                _ => Err(format!("unknown scope in:{} (try in:code or in:heading)", name)),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            blocks,
+            languages: query.languages.clone(),
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.blocks.is_empty() && self.languages.is_empty()
+    }
+
+    fn contains(&self, line: &RenderedLine) -> bool {
+        let in_block = self.blocks.is_empty()
+            || self.blocks.iter().any(|block| match block {
+                Block::Code => line.is_code_block && !line.is_code_fence,
+                Block::Heading => line.heading_level > 0,
+            });
+        let in_language = self.languages.is_empty()
+            || (!line.is_code_fence
+                && line
+                    .code_language
+                    .as_ref()
+                    .is_some_and(|language| self.languages.contains(language)));
+        in_block && in_language
+    }
+
+    /// The lines to search and their source line numbers: the document as
+    /// it is, or the text of the rendered lines within the scopes
+    fn lines<'a>(&self, content: &'a str, kind: DocKind) -> Vec<(usize, Cow<'a, str>)> {
+        if self.is_empty() {
+            return content.lines().map(Cow::Borrowed).enumerate().collect();
+        }
+        render_document(content, kind)
+            .iter()
+            .filter(|line| self.contains(line))
+            .map(|line| {
+                let text = line.to_plain_string();
+                // Code is drawn indented by two spaces, and headings after their `#`s
+                let marker = match line.heading_level {
+                    _ if line.is_code_block => "  ".to_string(),
+                    0 => String::new(),
+                    level => format!("{} ", "#".repeat(level as usize)),
+                };
+                let text = text.strip_prefix(marker.as_str()).map(str::to_string).unwrap_or(text);
+                (line.source_line, Cow::Owned(text))
+            })
+            .collect()
+    }
+}
+
+/// A `path:` filter: a glob, or else text the path has to contain. Paths are
+/// relative to the root, and a glob without a `/` matches file names.
+pub enum PathFilter {
+    Glob(GlobMatcher, bool), // Whether to match the whole relative path
+    Contains(String),
+}
+
+impl PathFilter {
+    fn new(text: &str) -> Result<Self, String> {
+        if !text.contains(['*', '?', '[', '{']) {
+            return Ok(Self::Contains(text.to_lowercase()));
+        }
+        GlobBuilder::new(text)
+            .case_insensitive(true)
+            .literal_separator(true)
+            .build()
+            .map(|glob| Self::Glob(glob.compile_matcher(), text.contains('/')))
+            .map_err(|e| e.to_string())
+    }
+
+    fn matches(&self, relative: &Path) -> bool {
+        match self {
+            Self::Glob(glob, true) => glob.is_match(relative),
+            Self::Glob(glob, false) => relative.file_name().is_some_and(|name| glob.is_match(name)),
+            Self::Contains(text) => relative.to_string_lossy().to_lowercase().contains(text),
+        }
+    }
+}
+
+/// Characters of context shown before a hit
+const CONTEXT_BEFORE: usize = 24;
+/// Characters of context shown in all, unless the match itself is longer
+const CONTEXT_WIDTH: usize = 80;
+
+/// The part of `line` around a match, with "…" where it is cut off, and
+/// where the matched `ranges` are within it. The context starts shortly
+/// before the first range; later ones past its end are left out.
+pub fn hit_context(line: &str, ranges: &[Range<usize>]) -> (String, Vec<Range<usize>>) {
+    let first = ranges.first().cloned().unwrap_or_default();
+    let text_start = line.len() - line.trim_start().len();
+    let text_end = line.trim_end().len().max(first.end);
+
+    let before = &line[text_start.min(first.start)..first.start];
+    let from = first.start - before.len()
+        + before.char_indices().rev().nth(CONTEXT_BEFORE - 1).map_or(0, |(idx, _)| idx);
+    let rest = &line[from..text_end];
+    let to = (from + rest.char_indices().nth(CONTEXT_WIDTH).map_or(rest.len(), |(idx, _)| idx)).max(first.end);
+
+    let prefix = if from > text_start { "…" } else { "" };
+    let suffix = if to < text_end { "…" } else { "" };
+    let offset = |idx: usize| prefix.len() + idx - from;
+    let highlights = ranges
+        .iter()
+        .filter(|range| range.start < to)
+        .map(|range| offset(range.start)..offset(range.end.min(to)))
+        .collect();
+    (format!("{}{}{}", prefix, &line[from..to], suffix), highlights)
+}
+
+// Fuzzy match scores, as in fzf
+const SCORE_MATCH: i64 = 16;
+const SCORE_GAP_START: i64 = -3;
+const SCORE_GAP_EXTENSION: i64 = -1;
+const BONUS_BOUNDARY: i64 = 8; // Matching the start of a word, or punctuation
+const BONUS_CAMEL: i64 = 7; // Matching a capital after a lowercase letter, or a digit after a letter
+const BONUS_CONSECUTIVE: i64 = 4;
+const BONUS_FIRST_CHAR_MULTIPLIER: i64 = 2;
+
+#[derive(Clone, Copy, PartialEq)]
+enum CharClass {
+    NonWord,
+    Lower,
+    Upper,
+    Digit,
+}
+
+fn char_class(c: char) -> CharClass {
+    if c.is_lowercase() {
+        CharClass::Lower
+    } else if c.is_uppercase() {
+        CharClass::Upper
+    } else if c.is_numeric() {
+        CharClass::Digit
+    } else if c.is_alphabetic() || c == '_' {
+        CharClass::Lower // Letters without case
+    } else {
+        CharClass::NonWord
+    }
+}
+
+/// Bonus for matching a character of `class` following one of `previous`
+fn boundary_bonus(previous: CharClass, class: CharClass) -> i64 {
+    match (previous, class) {
+        (_, CharClass::NonWord) | (CharClass::NonWord, _) => BONUS_BOUNDARY,
+        (CharClass::Lower, CharClass::Upper) => BONUS_CAMEL,
+        (CharClass::Lower | CharClass::Upper, CharClass::Digit) => BONUS_CAMEL,
+        _ => 0,
+    }
+}
+
+/// Match `term`'s characters in order anywhere in `text`, the way fzf does:
+/// the shortest match ending where the term is first complete, scored higher
+/// for consecutive characters and word starts and lower for gaps. Returns
+/// the score and the byte ranges of the matched characters.
+pub fn fuzzy_match(term: &str, text: &str, case_sensitive: bool) -> Option<(i64, Vec<Range<usize>>)> {
+    let fold = |c: char| if case_sensitive { c } else { c.to_lowercase().next().unwrap_or(c) };
+    let pattern: Vec<char> = term.chars().map(fold).collect();
+    if pattern.is_empty() {
+        return None;
+    }
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+
+    let mut matched = 0;
+    let end = chars.iter().position(|&(_, c)| {
+        if fold(c) == pattern[matched] {
+            matched += 1;
+        }
+        matched == pattern.len()
+    })? + 1;
+    let mut remaining = pattern.len();
+    let start = (0..end).rev().find(|&idx| {
+        if fold(chars[idx].1) == pattern[remaining - 1] {
+            remaining -= 1;
+        }
+        remaining == 0
+    })?;
+
+    let mut score = 0;
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    let mut previous = if start > 0 { char_class(chars[start - 1].1) } else { CharClass::NonWord };
+    let (mut matched, mut consecutive, mut first_bonus, mut in_gap) = (0, 0, 0, false);
+    for &(offset, c) in &chars[start..end] {
+        let class = char_class(c);
+        if matched < pattern.len() && fold(c) == pattern[matched] {
+            let mut bonus = boundary_bonus(previous, class);
+            if consecutive == 0 {
+                first_bonus = bonus;
+            } else {
+                // A run of matches keeps the bonus of the word start it began at
+                if bonus >= BONUS_BOUNDARY && bonus > first_bonus {
+                    first_bonus = bonus;
+                }
+                bonus = bonus.max(first_bonus).max(BONUS_CONSECUTIVE);
+            }
+            score += SCORE_MATCH + if matched == 0 { bonus * BONUS_FIRST_CHAR_MULTIPLIER } else { bonus };
+
+            let char_end = offset + c.len_utf8();
+            match ranges.last_mut() {
+                Some(last) if last.end == offset => last.end = char_end,
+                _ => ranges.push(offset..char_end),
+            }
+            matched += 1;
+            consecutive += 1;
+            in_gap = false;
+        } else {
+            score += if in_gap { SCORE_GAP_EXTENSION } else { SCORE_GAP_START };
+            consecutive = 0;
+            first_bonus = 0;
+            in_gap = true;
+        }
+        previous = class;
+    }
+    Some((score, ranges))
+}
+
+/// Fuzzy match every term in `text`, in any order: the summed score and
+/// the matched byte ranges, sorted and merged
+fn fuzzy_match_all(terms: &[String], text: &str, case_sensitive: bool) -> Option<(i64, Vec<Range<usize>>)> {
+    let mut score = 0;
+    let mut ranges = Vec::new();
+    for term in terms {
+        let (term_score, term_ranges) = fuzzy_match(term, text, case_sensitive)?;
+        score += term_score;
+        ranges.extend(term_ranges);
+    }
+    ranges.sort_by_key(|range: &Range<usize>| range.start);
+    let mut merged: Vec<Range<usize>> = Vec::new();
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    Some((score, merged))
+}
+
+/// The lowest score a fuzzy match counts with: half of what the terms'
+/// characters score on their own, so matches strewn across a long line with
+/// gaps between every character are left out
+fn fuzzy_threshold(terms: &[String]) -> i64 {
+    terms.iter().map(|term| term.chars().count() as i64).sum::<i64>() * SCORE_MATCH / 2
+}
+
+/// Byte ranges of the matches in `text`, leaving out empty ones (`a*` matches
+/// nothing at every position)
+pub fn find_ranges(pattern: &Regex, text: &str) -> Vec<Range<usize>> {
+    pattern
+        .find_iter(text)
+        .filter(|m| !m.is_empty())
+        .map(|m| m.range())
+        .collect()
+}
+
+pub fn is_match(pattern: &Regex, text: &str) -> bool {
+    pattern.find_iter(text).any(|m| !m.is_empty())
+}
+
+/// A one-line description of why a pattern doesn't compile. Syntax errors
+/// come with the pattern and a caret on the lines above.
+fn describe(error: &regex::Error) -> String {
+    match error {
+        regex::Error::Syntax(message) => message
+            .lines()
+            .last()
+            .unwrap_or_default()
+            .trim_start_matches("error: ")
+            .to_string(),
+        _ => error.to_string(),
+    }
+}
+
+/// A document found by the global search
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub path: PathBuf,
+    pub name: String,
+    pub title: Option<String>,
+    pub title_match: bool,
+    pub hits: Vec<SearchHit>, // Empty if only the title matched
+    pub score: i64,           // Best fuzzy score of its lines and title, in fuzzy mode
+}
+
+/// One occurrence of the query in a document
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub line: usize,                   // 0-based source line
+    pub occurrence: usize,             // Which match on the line this is, from 0
+    pub context: String,               // The line around the match
+    pub highlights: Vec<Range<usize>>, // The matched text within `context`
+}
+
+/// A document to search, and how it is rendered
+pub type SearchFile = (PathBuf, DocKind);
+
+/// What a background search looks for
+pub struct SearchJob {
+    files: Vec<SearchFile>,
+    matcher: Matcher,
+    scopes: Scopes,
+    ranking: Option<Ranking>,
+    order: ResultOrder,
+}
+
+/// How a search finds the lines that match
+enum Matcher {
+    /// Every occurrence of `hits` (`None` for a query of only filters), in
+    /// documents that match all of `required` somewhere
+    Patterns { hits: Option<Regex>, required: Vec<Regex> },
+    /// Lines containing each term's characters in order, scored like fzf
+    Fuzzy { terms: Vec<String>, case_sensitive: bool },
+}
+
+/// The order results should be listed in, as they arrive in tree order or
+/// already ranked
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResultOrder {
+    TitleMatchesFirst,
+    Ranked,
+    Score,
+}
+
+/// Narrowing down and ordering the documents with the index, for literal queries
+struct Ranking {
+    index: SharedIndex,
+    root: PathBuf,
+    terms: Vec<String>,
+    paths: Vec<PathFilter>,
+    tags: Vec<String>,
+}
+
+impl SearchJob {
+    /// A search for `query` in `files`. Literal queries are parsed for
+    /// phrases and filters and ranked with the index; a regex is matched
+    /// against every document in order. Regular expressions and fuzzy
+    /// queries take filters too, and fuzzy results are sorted by score.
+    pub fn new(
+        query: &str,
+        options: &SearchOptions,
+        files: Vec<SearchFile>,
+        root: &Path,
+        index: &SharedIndex,
+    ) -> Result<Self, String> {
+        let (query, regex) = if options.regex {
+            let (query, pattern) = Query::parse_regex(query);
+            (query, Some(pattern))
+        } else {
+            (Query::parse(query), None)
+        };
+        let paths = query.paths.iter().map(|glob| PathFilter::new(glob)).collect::<Result<Vec<_>, _>>()?;
+        let scopes = Scopes::new(&query)?;
+        // Without search terms, only filters need the index
+        let filters = |paths: Vec<PathFilter>, tags: Vec<String>| {
+            (!paths.is_empty() || !tags.is_empty()).then(|| Ranking {
+                index: index.clone(),
+                root: root.to_path_buf(),
+                terms: Vec::new(),
+                paths,
+                tags,
+            })
+        };
+
+        if let Some(pattern) = regex {
+            let hits = if pattern.is_empty() { None } else { Some(compile(&pattern, options)?) };
+            return Ok(Self {
+                files,
+                matcher: Matcher::Patterns { hits, required: Vec::new() },
+                scopes,
+                ranking: filters(paths, query.tags),
+                order: ResultOrder::TitleMatchesFirst,
+            });
+        }
+        if options.fuzzy {
+            let case_sensitive = !is_case_insensitive(&query.parts.concat(), options);
+            return Ok(Self {
+                files,
+                matcher: Matcher::Fuzzy { terms: query.parts, case_sensitive },
+                scopes,
+                ranking: filters(paths, query.tags),
+                order: ResultOrder::Score,
+            });
+        }
+
+        let patterns: Vec<String> = query
+            .parts
+            .iter()
+            .map(|part| {
+                let escaped: Vec<String> = part.split_whitespace().map(regex::escape).collect();
+                literal_pattern(escaped.join(r"\s+"), part, options)
+            })
+            .collect();
+        let required = patterns
+            .iter()
+            .map(|pattern| Regex::new(pattern))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| describe(&e))?;
+        let hits = if patterns.is_empty() {
+            None
+        } else {
+            Some(Regex::new(&patterns.join("|")).map_err(|e| describe(&e))?)
+        };
+        Ok(Self {
+            files,
+            matcher: Matcher::Patterns { hits, required },
+            scopes,
+            ranking: Some(Ranking {
+                index: index.clone(),
+                root: root.to_path_buf(),
+                terms: query.parts.iter().flat_map(|part| index::words(part)).collect(),
+                paths,
+                tags: query.tags,
+            }),
+            order: ResultOrder::Ranked,
+        })
+    }
+}
+
+impl Ranking {
+    /// The documents that can match, best first. Brings the index up to date
+    /// first, reporting progress. `None` if cancelled.
+    fn candidates(
+        &self,
+        files: Vec<SearchFile>,
+        tx: &Sender<SearchEvent>,
+        cancelled: &AtomicBool,
+    ) -> Option<Vec<SearchFile>> {
+        let paths: Vec<PathBuf> = files.iter().map(|(path, _)| path.clone()).collect();
+        let guard = self.index.lock_fresh(&self.root, &paths, cancelled, |done, total| {
+            let _ = tx.send(SearchEvent::Indexing(done, total));
+        })?;
+        let index = guard.as_ref()?;
+
+        // Only documents still listed, in case the index is behind
+        let kinds: HashMap<PathBuf, DocKind> = files.into_iter().collect();
+        let ranked = index.rank(&self.terms, |path, tags| kinds.contains_key(path) && self.keep(path, tags));
+        Some(
+            ranked
+                .into_iter()
+                .filter_map(|path| kinds.get(&path).map(|&kind| (path, kind)))
+                .collect(),
+        )
+    }
+
+    fn keep(&self, path: &Path, tags: &[String]) -> bool {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        self.paths.iter().all(|filter| filter.matches(relative)) && self.tags.iter().all(|tag| tags.contains(tag))
+    }
+}
+
+/// Progress reported by a background search
+#[derive(Debug)]
+pub enum SearchEvent {
+    /// Documents indexed so far, out of those new or changed since last time
+    Indexing(usize, usize),
+    /// How many documents are going to be searched
+    Candidates(usize),
+    /// A ranked search found as many results as were wanted, and waits
+    /// until more are (see `Searcher::read_more`)
+    Waiting,
+    /// A document matched
+    Result(SearchResult),
+    /// A document was searched (whether or not it matched)
+    Scanned,
+    /// Every document has been searched
+    Finished,
+}
+
+/// Results a ranked search finds before it waits to be asked for more
+const RESULTS_PAGE: usize = 100;
+
+/// Searches documents in a background thread, streaming results as they are
+/// found. A ranked search only reads documents as far down the ranking as
+/// results are wanted, so a common word doesn't read the whole tree.
+/// Dropping it cancels the search, so a stale query stops as soon as the
+/// next one starts.
+pub struct Searcher {
+    rx: Receiver<SearchEvent>,
+    cancelled: Arc<AtomicBool>,
+    wanted: Arc<AtomicUsize>,
+    worker: Thread,
+    pub order: ResultOrder,
+}
+
+impl Searcher {
+    pub fn start(job: SearchJob) -> Self {
+        let (tx, rx) = channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let wanted = Arc::new(AtomicUsize::new(RESULTS_PAGE));
+        let flag = Arc::clone(&cancelled);
+        let limit = Arc::clone(&wanted);
+        let order = job.order;
+
+        let worker = thread::spawn(move || {
+            let SearchJob { files, matcher, scopes, ranking, .. } = job;
+            let files = match ranking {
+                Some(ranking) => match ranking.candidates(files, &tx, &flag) {
+                    Some(files) => files,
+                    None => return,
+                },
+                None => files,
+            };
+            if tx.send(SearchEvent::Candidates(files.len())).is_err() {
+                return;
+            }
+
+            let mut found = 0;
+            for (path, kind) in files {
+                if order == ResultOrder::Ranked && found >= limit.load(Ordering::Relaxed) {
+                    if tx.send(SearchEvent::Waiting).is_err() {
+                        return;
+                    }
+                    while found >= limit.load(Ordering::Relaxed) && !flag.load(Ordering::Relaxed) {
+                        thread::park();
+                    }
+                }
+                if flag.load(Ordering::Relaxed) {
+                    return;
+                }
+                if let Ok(content) = fs::read_to_string(&path) {
+                    if let Some(result) = search_document(path, &content, kind, &matcher, &scopes) {
+                        found += 1;
+                        if tx.send(SearchEvent::Result(result)).is_err() {
+                            return;
+                        }
+                    }
+                }
+                if tx.send(SearchEvent::Scanned).is_err() {
+                    return;
+                }
+            }
+            let _ = tx.send(SearchEvent::Finished);
+        })
+        .thread()
+        .clone();
+
+        Self {
+            rx,
+            cancelled,
+            wanted,
+            worker,
+            order,
+        }
+    }
+
+    /// Let a waiting ranked search go on to the next page of results
+    pub fn read_more(&self) {
+        self.wanted.fetch_add(RESULTS_PAGE, Ordering::Relaxed);
+        self.worker.unpark();
+    }
+
+    /// Drain all pending events without blocking
+    pub fn poll_events(&self) -> Vec<SearchEvent> {
+        self.rx.try_iter().collect()
+    }
+}
+
+impl Drop for Searcher {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.worker.unpark();
+    }
+}
+
+/// Every matching line of a document, if it has any or its title matches.
+/// Without anything to match, every document that gets this far (and has
+/// something in the scopes) is a result.
+fn search_document(
+    path: PathBuf,
+    content: &str,
+    kind: DocKind,
+    matcher: &Matcher,
+    scopes: &Scopes,
+) -> Option<SearchResult> {
+    let lines = scopes.lines(content, kind);
+    let title = if kind != DocKind::PlainText { title::extract(content) } else { None };
+    // The title is only searched along with the whole document
+    let searched_title = title.as_deref().filter(|_| scopes.is_empty());
+    let matches_anything = matches!(matcher, Matcher::Patterns { hits: Some(_), .. })
+        || matches!(matcher, Matcher::Fuzzy { terms, .. } if !terms.is_empty());
+    if !matches_anything {
+        if lines.is_empty() {
+            return None;
+        }
+        return Some(SearchResult::new(path, title, false, Vec::new(), 0));
+    }
+
+    let (hits, title_match, score) = match matcher {
+        Matcher::Patterns { hits, required } => {
+            let searched: Cow<str> = if scopes.is_empty() {
+                Cow::Borrowed(content)
+            } else {
+                Cow::Owned(lines.iter().map(|(_, text)| text.as_ref()).collect::<Vec<_>>().join("\n"))
+            };
+            if !required.iter().all(|pattern| is_match(pattern, &searched)) {
+                return None;
+            }
+            let pattern = hits.as_ref()?;
+            let title_match = searched_title.is_some_and(|t| is_match(pattern, t));
+            let hits = lines
+                .iter()
+                .flat_map(|(line_no, line)| {
+                    find_ranges(pattern, line)
+                        .into_iter()
+                        .enumerate()
+                        .map(|(occurrence, range)| SearchHit::new(*line_no, occurrence, line, &[range]))
+                })
+                .collect();
+            (hits, title_match, 0)
+        }
+        Matcher::Fuzzy { terms, case_sensitive } => {
+            let threshold = fuzzy_threshold(terms);
+            let score_of = |text: &str| {
+                fuzzy_match_all(terms, text, *case_sensitive).filter(|(score, _)| *score >= threshold)
+            };
+            let title_score = searched_title.and_then(score_of);
+            let mut best = title_score.as_ref().map_or(i64::MIN, |(score, _)| *score);
+            let mut scored = Vec::new();
+            for (line_no, line) in &lines {
+                if let Some((score, ranges)) = score_of(line) {
+                    best = best.max(score);
+                    scored.push((score, SearchHit::new(*line_no, 0, line, &ranges)));
+                }
+            }
+            // Best lines first, ties in document order
+            scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+            let hits: Vec<SearchHit> = scored.into_iter().map(|(_, hit)| hit).collect();
+            (hits, title_score.is_some(), best)
+        }
+    };
+
+    if hits.is_empty() && !title_match {
+        return None;
+    }
+    Some(SearchResult::new(path, title, title_match, hits, score))
+}
+
+impl SearchResult {
+    fn new(path: PathBuf, title: Option<String>, title_match: bool, hits: Vec<SearchHit>, score: i64) -> Self {
+        let name = path
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        Self {
+            path,
+            name,
+            title,
+            title_match,
+            hits,
+            score,
+        }
+    }
+}
+
+impl SearchHit {
+    fn new(line_no: usize, occurrence: usize, line: &str, ranges: &[Range<usize>]) -> Self {
+        let (context, highlights) = hit_context(line, ranges);
+        Self {
+            line: line_no,
+            occurrence,
+            context,
+            highlights,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_parses_words_phrases_and_filters() {
+        let query = Query::parse(r#"deploy "connection pool" path:runbooks/** tag:Ops in:code lang:Rust"#);
+        assert_eq!(query.parts, ["deploy", "connection pool"]);
+        assert_eq!(query.paths, ["runbooks/**"]);
+        assert_eq!(query.tags, ["ops"]);
+        assert_eq!(query.blocks, ["code"]);
+        assert_eq!(query.languages, ["rust"]);
+    }
+
+    #[test]
+    fn unterminated_phrase_runs_to_the_end() {
+        let query = Query::parse(r#"retry "exponential back"#);
+        assert_eq!(query.parts, ["retry", "exponential back"]);
+    }
+
+    #[test]
+    fn empty_phrases_and_filters_are_not_filters() {
+        let query = Query::parse(r#""" "  " path: tag:"#);
+        assert_eq!(query.parts, ["path:", "tag:"]);
+        assert!(query.paths.is_empty() && query.tags.is_empty());
+    }
+
+    #[test]
+    fn regex_keeps_its_spacing_without_the_filters_and_scopes() {
+        let (query, pattern) = Query::parse_regex(r"fn  \w+ in:code \s*\(  lang:Rust path:src tag:Ops");
+        assert_eq!(query.blocks, ["code"]);
+        assert_eq!(query.languages, ["rust"]);
+        assert_eq!(query.paths, ["src"]);
+        assert_eq!(query.tags, ["ops"]);
+        assert_eq!(pattern, r"fn  \w+ \s*\(");
+
+        let (query, pattern) = Query::parse_regex(" a  b ");
+        assert_eq!(query, Query::default());
+        assert_eq!(pattern, " a  b ");
+    }
+
+    #[test]
+    fn regex_applies_path_filters() {
+        let options = SearchOptions { regex: true, ..SearchOptions::default() };
+        let job = SearchJob::new(r"fn \w+ path:src/**", &options, Vec::new(), Path::new("/"), &SharedIndex::default())
+            .unwrap();
+        let ranking = job.ranking.unwrap();
+        assert!(ranking.keep(Path::new("/src/main.md"), &[]));
+        assert!(!ranking.keep(Path::new("/docs/main.md"), &[]));
+        let Matcher::Patterns { hits: Some(pattern), .. } = job.matcher else {
+            panic!("expected a pattern");
+        };
+        assert!(!pattern.as_str().contains("path:"));
+    }
+
+    #[test]
+    fn anchored_regex_matches_heading_text() {
+        let options = SearchOptions { regex: true, ..SearchOptions::default() };
+        let job = SearchJob::new("^Install in:heading", &options, Vec::new(), Path::new("/"), &SharedIndex::default())
+            .unwrap();
+        let content = "# Install\n\nInstall it first.\n\n## Installing more\n\n## Then Install\n";
+        let result = search_document(PathBuf::from("/a.md"), content, DocKind::Markdown, &job.matcher, &job.scopes)
+            .unwrap();
+        let lines: Vec<usize> = result.hits.iter().map(|hit| hit.line).collect();
+        assert_eq!(lines, [0, 4]);
+        assert!(result.hits.iter().all(|hit| !hit.context.starts_with('#')));
+    }
+}
@@ -0,0 +1,39 @@
+use std::io::{self, Read};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+
+/// Read stdin in a background thread, sending text as it arrives so output
+/// from a slow command shows up while it is still being produced.
+/// The channel disconnects at end of input.
+pub fn read_in_background() -> Receiver<String> {
+    let (tx, rx) = channel();
+    thread::spawn(move || {
+        let mut stdin = io::stdin().lock();
+        let mut buf = [0u8; 8192];
+        let mut pending = Vec::new(); // May end in the middle of a character
+        loop {
+            let len = match stdin.read(&mut buf) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            };
+            pending.extend_from_slice(&buf[..len]);
+
+            // Hold back an incomplete trailing character until the rest arrives
+            let complete = match std::str::from_utf8(&pending) {
+                Err(e) if e.error_len().is_none() => e.valid_up_to(),
+                _ => pending.len(),
+            };
+            let text = String::from_utf8_lossy(&pending[..complete]).into_owned();
+            pending.drain(..complete);
+            if !text.is_empty() && tx.send(text).is_err() {
+                return;
+            }
+        }
+        if !pending.is_empty() {
+            let _ = tx.send(String::from_utf8_lossy(&pending).into_owned());
+        }
+    });
+    rx
+}
@@ -0,0 +1,65 @@
+use crate::app::App;
+
+/// One app per path given on the command line, shown one at a time
+pub struct Tabs {
+    pub apps: Vec<App>,
+    pub active: usize,
+}
+
+impl Tabs {
+    pub fn new(apps: Vec<App>) -> Self {
+        Self { apps, active: 0 }
+    }
+
+    pub fn active_mut(&mut self) -> &mut App {
+        &mut self.apps[self.active]
+    }
+
+    /// Go to the next tab, or with a count to tab `count` (1-based), like vim's `gt`
+    pub fn select_next(&mut self, count: Option<usize>) {
+        self.active = match count {
+            Some(n) => n.clamp(1, self.apps.len()) - 1,
+            None => (self.active + 1) % self.apps.len(),
+        };
+    }
+
+    /// Go back `count` tabs, wrapping around, like vim's `gT`
+    pub fn select_previous(&mut self, count: Option<usize>) {
+        let len = self.apps.len();
+        self.active = (self.active + len - count.unwrap_or(1) % len) % len;
+    }
+
+    /// Apply filesystem changes, scan progress, git status, stdin and search results for every
+    /// tab. Returns true if the active tab needs to be redrawn.
+    pub fn process_background(&mut self) -> bool {
+        let mut active_changed = false;
+        for (idx, app) in self.apps.iter_mut().enumerate() {
+            let fs_changed = app.process_fs_changes();
+            let scan_progressed = app.process_scan_events();
+            let git_updated = app.process_git_status();
+            let stdin_read = app.process_stdin();
+            let search_progressed = app.process_search_events();
+            if idx == self.active {
+                active_changed = fs_changed || scan_progressed || git_updated || stdin_read || search_progressed;
+            }
+        }
+        active_changed
+    }
+
+    /// Whether any tab is waiting on a background scan, search or stdin
+    pub fn is_busy(&self) -> bool {
+        self.apps.iter().any(|app| app.is_scanning() || app.is_searching() || app.is_reading_stdin())
+    }
+
+    /// Tab bar labels: the open document, or the root directory
+    pub fn labels(&self) -> Vec<String> {
+        self.apps
+            .iter()
+            .map(|app| match app.current_file {
+                Some(ref path) => path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+                None if app.from_stdin => "stdin".to_string(),
+                None => app.tree.name.clone(),
+            })
+            .collect()
+    }
+}
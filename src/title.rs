@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use pulldown_cmark::{Event, HeadingLevel, Parser, Tag, TagEnd};
+
+use crate::frontmatter;
+
+/// How much of a file is read to find its title
+const HEAD_BYTES: u64 = 8192;
+
+/// A document's title: its front-matter `title`, or else its first H1
+pub fn extract(content: &str) -> Option<String> {
+    if let Some(title) = frontmatter::parse(content).and_then(|fm| fm.title) {
+        return Some(title);
+    }
+
+    let mut title: Option<String> = None;
+    for event in Parser::new(frontmatter::strip(content)) {
+        match event {
+            Event::Start(Tag::Heading { level: HeadingLevel::H1, .. }) => title = Some(String::new()),
+            Event::End(TagEnd::Heading(HeadingLevel::H1)) => break,
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(ref mut title) = title {
+                    title.push_str(&text);
+                }
+            }
+            _ => {}
+        }
+    }
+    title.map(|t| t.trim().to_string()).filter(|t| !t.is_empty())
+}
+
+/// Read the title from the start of a file, without loading all of it
+pub fn read(path: &Path) -> Option<String> {
+    let mut buf = Vec::new();
+    File::open(path).ok()?.take(HEAD_BYTES).read_to_end(&mut buf).ok()?;
+    // The buffer may end in the middle of a character
+    let text = match std::str::from_utf8(&buf) {
+        Ok(text) => text,
+        Err(e) => std::str::from_utf8(&buf[..e.valid_up_to()]).ok()?,
+    };
+    extract(text)
+}
+
+/// Titles already read, dropped again when the watcher reports a change
+#[derive(Debug, Default)]
+pub struct TitleCache {
+    titles: HashMap<PathBuf, Option<String>>,
+}
+
+impl TitleCache {
+    pub fn get(&mut self, path: &Path) -> Option<String> {
+        self.titles.entry(path.to_path_buf()).or_insert_with(|| read(path)).clone()
+    }
+
+    /// Remember a title extracted from contents that were read anyway
+    pub fn insert(&mut self, path: &Path, title: Option<String>) {
+        self.titles.insert(path.to_path_buf(), title);
+    }
+
+    pub fn invalidate(&mut self, path: &Path) {
+        self.titles.remove(path);
+    }
+}
@@ -1,3 +1,5 @@
+use std::ops::Range;
+
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
@@ -6,8 +8,12 @@ use ratatui::{
     Frame,
 };
 
-use crate::app::{App, AppMode, Focus, Theme};
+use crate::app::{self, App, AppMode, Focus, PaneAreas, SearchRow, Theme};
+use crate::file_tree::TreeNode;
+use crate::git::GitStatus;
 use crate::markdown::{RenderedLine, TextSegment};
+use crate::tabs::Tabs;
+use crate::util::format_date;
 
 // Theme color definitions
 struct ThemeColors {
@@ -68,7 +74,36 @@ fn get_theme_colors(theme: Theme) -> ThemeColors {
     }
 }
 
-pub fn draw(f: &mut Frame, app: &mut App) {
+pub fn draw(f: &mut Frame, tabs: &mut Tabs) {
+    // A tab bar on top when more than one path was opened
+    let area = if tabs.apps.len() > 1 {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0)])
+            .split(f.area());
+        draw_tab_bar(f, tabs, chunks[0]);
+        chunks[1]
+    } else {
+        f.area()
+    };
+    draw_app(f, tabs.active_mut(), area);
+}
+
+fn draw_tab_bar(f: &mut Frame, tabs: &Tabs, area: Rect) {
+    let mut spans = Vec::new();
+    for (idx, label) in tabs.labels().into_iter().enumerate() {
+        let style = if idx == tabs.active {
+            Style::default().bg(Color::Blue).fg(Color::White).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(Color::DarkGray)
+        };
+        spans.push(Span::styled(format!(" {} {} ", idx + 1, label), style));
+        spans.push(Span::raw(" "));
+    }
+    f.render_widget(Paragraph::new(Line::from(spans)), area);
+}
+
+fn draw_app(f: &mut Frame, app: &mut App, area: Rect) {
     // Split into main area and bottom bar
     let main_chunks = Layout::default()
         .direction(Direction::Vertical)
@@ -76,31 +111,45 @@ pub fn draw(f: &mut Frame, app: &mut App) {
             Constraint::Min(3),
             Constraint::Length(1),
         ])
-        .split(f.area());
+        .split(area);
 
-    // In select mode, show only content (full width for clean text selection)
-    if app.mode == AppMode::Select {
-        app.set_content_height(main_chunks[0].height);
-        draw_content(f, app, main_chunks[0]);
-        draw_status_bar(f, app, main_chunks[1]);
-        return;
-    }
+    app.layout_width = main_chunks[0].width;
+    app.areas = PaneAreas::default();
 
-    // Split main area into sidebar and content
-    let chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([
-            Constraint::Percentage(30),
-            Constraint::Percentage(70),
-        ])
-        .split(main_chunks[0]);
-
-    app.set_content_height(chunks[1].height);
+    // Without the sidebar (hidden, collapsed on a narrow terminal, or in select
+    // mode for clean text selection), the content takes the full width
+    let content_area = if !app.sidebar_shown() {
+        main_chunks[0]
+    } else {
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Length(app.sidebar_width()),
+                Constraint::Min(1),
+            ])
+            .split(main_chunks[0]);
 
-    draw_sidebar(f, app, chunks[0]);
-    draw_content(f, app, chunks[1]);
+        app.fit_sidebar_scroll(chunks[0].height.saturating_sub(2) as usize);
+        app.areas.sidebar = Some(chunks[0]);
+        draw_sidebar(f, app, chunks[0]);
+        chunks[1]
+    };
+    app.set_content_height(content_area.height);
+    record_content_areas(app, content_area);
+    draw_content(f, app, content_area);
     draw_status_bar(f, app, main_chunks[1]);
 
+    // Remember where overlay lists are, so they can be clicked
+    app.areas.overlay_list = match app.mode {
+        AppMode::Search => {
+            let list = inner(search_overlay_chunks(f.area())[1]);
+            app.fit_search_scroll(list.height as usize);
+            Some(list)
+        }
+        AppMode::Settings => Some(inner(settings_overlay_rect(f.area()))),
+        _ => None,
+    };
+
     // Draw search overlay if in search mode
     if app.mode == AppMode::Search {
         draw_search_overlay(f, app, f.area());
@@ -117,8 +166,106 @@ pub fn draw(f: &mut Frame, app: &mut App) {
     }
 }
 
+/// Record the document text and scrollbar areas of the content pane
+fn record_content_areas(app: &mut App, area: Rect) {
+    let (_, content_area) = split_doc_search(app, area);
+    app.areas.content = inner(content_area);
+    if app.total_wrapped_lines() > content_area.height.saturating_sub(2) as usize {
+        app.areas.scrollbar = Some(Rect {
+            x: content_area.x + content_area.width.saturating_sub(1),
+            y: content_area.y + 1,
+            width: 1,
+            height: content_area.height.saturating_sub(2),
+        });
+    }
+}
+
+/// The area inside a block's borders
+fn inner(area: Rect) -> Rect {
+    Rect {
+        x: area.x + 1,
+        y: area.y + 1,
+        width: area.width.saturating_sub(2),
+        height: area.height.saturating_sub(2),
+    }
+}
+
+/// A centered rectangle covering the given fractions of `area`
+fn overlay_rect(area: Rect, width: f32, height: f32) -> Rect {
+    let overlay_width = (area.width as f32 * width) as u16;
+    let overlay_height = (area.height as f32 * height) as u16;
+    let overlay_x = (area.width - overlay_width) / 2;
+    let overlay_y = (area.height - overlay_height) / 2;
+    Rect::new(overlay_x, overlay_y, overlay_width, overlay_height)
+}
+
 fn draw_status_bar(f: &mut Frame, app: &App, area: Rect) {
-    let help_text = if app.mode == AppMode::Select {
+    let help_text = if app.mode == AppMode::Command {
+        // Show the command line and any completion candidates
+        let mut spans = vec![
+            Span::raw(format!(":{}", app.command_line)),
+            Span::styled("_", Style::default().add_modifier(Modifier::SLOW_BLINK)),
+        ];
+        if app.command_completions.len() > 1 {
+            spans.push(Span::raw("   "));
+            for (idx, candidate) in app.command_completions.iter().enumerate() {
+                let style = if Some(idx) == app.command_completion_index {
+                    Style::default().fg(Color::Black).bg(Color::White)
+                } else {
+                    Style::default().fg(Color::Gray)
+                };
+                let label = candidate.rsplit([' ', '/']).find(|s| !s.is_empty()).unwrap_or(candidate);
+                spans.push(Span::styled(format!(" {} ", label), style));
+            }
+        }
+        Line::from(spans)
+    } else if app.mode == AppMode::FileOp {
+        let confirmation = app.file_op.as_ref().is_some_and(|op| op.is_confirmation());
+        let mut spans = vec![Span::styled(
+            format!(" {} ", app.file_op_prompt()),
+            Style::default().fg(Color::Black).bg(Color::Rgb(253, 151, 31)),
+        )];
+        if !confirmation {
+            spans.push(Span::raw(format!(" {}", app.file_op_input)));
+            spans.push(Span::styled("_", Style::default().add_modifier(Modifier::SLOW_BLINK)));
+        }
+        Line::from(spans)
+    } else if let Some(ref message) = app.status_message {
+        Line::from(Span::styled(
+            format!(" {} ", message),
+            Style::default().fg(Color::White).bg(Color::Red),
+        ))
+    } else if let Some((name, error)) = app.selected_error() {
+        Line::from(Span::styled(
+            format!(" {}: {} ", name, error),
+            Style::default().fg(Color::White).bg(Color::Red),
+        ))
+    } else if app.keys.is_pending() {
+        Line::from(vec![
+            Span::styled(
+                format!(" {} ", app.keys.pending_text()),
+                Style::default().fg(Color::Black).bg(Color::Rgb(253, 151, 31)),
+            ),
+            Span::raw(" Waiting for motion (Esc to cancel) "),
+        ])
+    } else if app.mode == AppMode::Normal && app.has_doc_search() {
+        // A document search kept after its prompt closed
+        let position = match app.doc_search_matches.len() {
+            0 => "no matches".to_string(),
+            n => format!("{}/{}", app.doc_search_current + 1, n),
+        };
+        Line::from(vec![
+            Span::styled(
+                format!(" /{} ", app.doc_search_query),
+                Style::default().fg(Color::Black).bg(Color::Rgb(253, 151, 31)),
+            ),
+            Span::raw(format!(" {}  ", position)),
+            Span::styled(" n/N ", Style::default().fg(Color::Black).bg(Color::White)),
+            Span::raw(" Next/prev match  "),
+            Span::styled(" Esc ", Style::default().fg(Color::Black).bg(Color::White)),
+            Span::raw(" Clear "),
+        ])
+    } else if app.mode == AppMode::Select {
         // Show select mode help
         Line::from(vec![
             Span::styled(" SELECT MODE ", Style::default().fg(Color::Black).bg(Color::Rgb(253, 151, 31))),
@@ -140,6 +287,8 @@ fn draw_status_bar(f: &mut Frame, app: &App, area: Rect) {
             Span::raw(" Full  "),
             Span::styled(" / ", Style::default().fg(Color::Black).bg(Color::White)),
             Span::raw(" Find  "),
+            Span::styled(" : ", Style::default().fg(Color::Black).bg(Color::White)),
+            Span::raw(" Cmd  "),
             Span::styled(" ^s ", Style::default().fg(Color::Black).bg(Color::White)),
             Span::raw(" Search  "),
             Span::styled(" v ", Style::default().fg(Color::Black).bg(Color::White)),
@@ -157,12 +306,38 @@ fn draw_status_bar(f: &mut Frame, app: &App, area: Rect) {
     f.render_widget(paragraph, area);
 }
 
+fn git_color(status: GitStatus) -> Color {
+    match status {
+        GitStatus::Modified => Color::Rgb(230, 219, 116),
+        GitStatus::Added => Color::Rgb(166, 226, 46),
+        GitStatus::Untracked => Color::Rgb(249, 38, 114),
+    }
+}
+
+/// Markers after a sidebar entry's name: symlink, unreadable, or still being scanned
+fn node_markers(node: &TreeNode) -> Vec<Span<'static>> {
+    let mut markers = Vec::new();
+    if node.symlink {
+        markers.push(Span::styled(" →", Style::default().fg(Color::Cyan)));
+    }
+    if node.error.is_some() {
+        markers.push(Span::styled(" !", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)));
+    } else if node.scanning {
+        // Not yet known whether the directory holds any documents
+        markers.push(Span::styled(" …", Style::default().fg(Color::DarkGray)));
+    }
+    markers
+}
+
 fn draw_sidebar(f: &mut Frame, app: &App, area: Rect) {
     let items = app.visible_items();
+    let filter_terms = app.filter_terms();
+    let inner_width = area.width.saturating_sub(2) as usize;
 
     let list_items: Vec<ListItem> = items
         .iter()
         .enumerate()
+        .skip(app.sidebar_scroll)
         .map(|(idx, node)| {
             let indent = "  ".repeat(node.depth);
             let icon = if node.is_dir {
@@ -175,6 +350,7 @@ fn draw_sidebar(f: &mut Frame, app: &App, area: Rect) {
                 "  "
             };
 
+            let git_status = app.git_status.get(&node.path);
             let style = if idx == app.selected_index {
                 Style::default()
                     .bg(if app.focus == Focus::Sidebar {
@@ -184,17 +360,76 @@ fn draw_sidebar(f: &mut Frame, app: &App, area: Rect) {
                     })
                     .fg(Color::White)
                     .add_modifier(Modifier::BOLD)
+            } else if node.error.is_some() {
+                Style::default().fg(Color::Red)
+            } else if node.ignored {
+                Style::default().fg(Color::DarkGray)
+            } else if let (false, Some(status)) = (node.is_dir, git_status) {
+                Style::default().fg(git_color(status))
             } else if node.is_dir {
                 Style::default().fg(Color::Yellow)
             } else {
                 Style::default().fg(Color::White)
             };
 
-            let content = format!("{}{}{}", indent, icon, node.name);
-            ListItem::new(Line::from(Span::styled(content, style)))
+            // Right-aligned metadata and git status columns, with the name truncated to make room
+            let columns = sidebar_columns(app, node);
+            let git_column = if app.git_status.is_empty() {
+                String::new()
+            } else {
+                format!(" {}", git_status.map(|s| s.marker()).unwrap_or(' '))
+            };
+            let right_width = columns.chars().count() + git_column.chars().count();
+            let prefix = format!("{}{}", indent, icon);
+            let name_width = inner_width
+                .saturating_sub(prefix.chars().count())
+                .saturating_sub(right_width)
+                .saturating_sub(node_markers(node).iter().map(|m| m.width()).sum());
+            let label = node.label();
+            let name: String = if label.chars().count() > name_width && right_width > 0 {
+                let mut name: String = label.chars().take(name_width.saturating_sub(1)).collect();
+                name.push('…');
+                name
+            } else {
+                label.to_string()
+            };
+
+            let mut spans = vec![Span::styled(prefix, style)];
+            if filter_terms.is_empty() {
+                spans.push(Span::styled(name.clone(), style));
+            } else {
+                let match_style = style.fg(Color::Rgb(253, 151, 31)).add_modifier(Modifier::BOLD);
+                spans.extend(highlight_terms(&name, &filter_terms, style, match_style));
+            }
+            for marker in node_markers(node) {
+                spans.push(marker);
+            }
+            if right_width > 0 {
+                let padding = name_width.saturating_sub(name.chars().count());
+                let column_style = if idx == app.selected_index { style } else { Style::default().fg(Color::DarkGray) };
+                spans.push(Span::styled(" ".repeat(padding), style));
+                spans.push(Span::styled(columns, column_style));
+            }
+            if let Some(status) = git_status {
+                let git_style = if idx == app.selected_index { style } else { Style::default().fg(git_color(status)) };
+                spans.push(Span::styled(git_column, git_style.add_modifier(Modifier::BOLD)));
+            }
+            ListItem::new(Line::from(spans))
         })
         .collect();
 
+    let title = if app.mode == AppMode::Filter {
+        format!(" Filter: {}_ ", app.sidebar_filter)
+    } else if !app.sidebar_filter.is_empty() {
+        format!(" Files /{} ", app.sidebar_filter)
+    } else {
+        format!(
+            " Files{}{} ",
+            if app.show_ignored { " (all)" } else { "" },
+            if app.is_scanning() { " (scanning…)" } else { "" }
+        )
+    };
+
     let border_style = if app.focus == Focus::Sidebar {
         Style::default().fg(Color::Blue)
     } else {
@@ -206,15 +441,73 @@ fn draw_sidebar(f: &mut Frame, app: &App, area: Rect) {
             Block::default()
                 .borders(Borders::ALL)
                 .border_style(border_style)
-                .title(" Files "),
+                .title(title),
         );
 
     f.render_widget(list, area);
 }
 
-fn draw_content(f: &mut Frame, app: &App, area: Rect) {
-    // If in document search mode, split area for search bar
-    let (search_area, content_area) = if app.mode == AppMode::DocumentSearch {
+/// Text of the enabled metadata columns for a sidebar row
+fn sidebar_columns(app: &App, node: &TreeNode) -> String {
+    let mut columns = String::new();
+    if app.settings.show_word_count_column {
+        let words = match node.meta.word_count {
+            Some(n) if n >= 10_000 => format!("{}k", n / 1000),
+            Some(n) if n >= 1000 => format!("{:.1}k", n as f64 / 1000.0),
+            Some(n) => n.to_string(),
+            None => String::new(),
+        };
+        columns.push_str(&format!(" {:>5}", words));
+    }
+    if app.settings.show_modified_column {
+        let date = node.meta.modified.map(format_date).unwrap_or_default();
+        columns.push_str(&format!(" {:>10}", date));
+    }
+    columns
+}
+
+/// Split `text` into spans, styling every case-insensitive occurrence of the terms
+fn highlight_terms(text: &str, terms: &[String], style: Style, match_style: Style) -> Vec<Span<'static>> {
+    let chars: Vec<char> = text.chars().collect();
+    let lower: Vec<char> = chars
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect();
+    let mut matched = vec![false; chars.len()];
+
+    for term in terms {
+        let term: Vec<char> = term.chars().collect();
+        if term.is_empty() || term.len() > lower.len() {
+            continue;
+        }
+        for start in 0..=lower.len() - term.len() {
+            if lower[start..start + term.len()] == term[..] {
+                matched[start..start + term.len()].iter_mut().for_each(|m| *m = true);
+            }
+        }
+    }
+
+    let mut spans = Vec::new();
+    let mut current = String::new();
+    let mut current_matched = false;
+    for (c, is_match) in chars.into_iter().zip(matched) {
+        if is_match != current_matched && !current.is_empty() {
+            let s = if current_matched { match_style } else { style };
+            spans.push(Span::styled(std::mem::take(&mut current), s));
+        }
+        current_matched = is_match;
+        current.push(c);
+    }
+    if !current.is_empty() {
+        let s = if current_matched { match_style } else { style };
+        spans.push(Span::styled(current, s));
+    }
+    spans
+}
+
+/// In document search mode, split off the area for the search bar
+fn split_doc_search(app: &App, area: Rect) -> (Option<Rect>, Rect) {
+    if app.mode == AppMode::DocumentSearch {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
@@ -225,7 +518,11 @@ fn draw_content(f: &mut Frame, app: &App, area: Rect) {
         (Some(chunks[0]), chunks[1])
     } else {
         (None, area)
-    };
+    }
+}
+
+fn draw_content(f: &mut Frame, app: &App, area: Rect) {
+    let (search_area, content_area) = split_doc_search(app, area);
 
     // Draw search bar if in document search mode
     if let Some(search_rect) = search_area {
@@ -239,16 +536,21 @@ fn draw_content(f: &mut Frame, app: &App, area: Rect) {
             format!(" ({}/{})", app.doc_search_current + 1, app.doc_search_matches.len())
         };
 
-        let search_input = Paragraph::new(Line::from(vec![
+        let mut spans = vec![
             Span::raw(&app.doc_search_query),
             Span::styled("_", Style::default().add_modifier(Modifier::SLOW_BLINK)),
             Span::styled(&match_info, Style::default().fg(Color::Rgb(117, 113, 94))),
-        ]))
+        ];
+        spans.extend(search_error_span(app));
+        let search_input = Paragraph::new(Line::from(spans))
         .block(
             Block::default()
                 .borders(Borders::ALL)
                 .border_style(Style::default().fg(Color::Rgb(253, 151, 31)))
-                .title(" Find (Enter/^n: next, ^p: prev, Esc: close) "),
+                .title(format!(
+                    " Find [{}] (Enter/^n: next, ^p: prev, ^r: regex, M-c: case, M-w: word, Esc: close) ",
+                    app.settings.search.label(false)
+                )),
         )
         .style(Style::default().fg(Color::White));
 
@@ -263,6 +565,8 @@ fn draw_content(f: &mut Frame, app: &App, area: Rect) {
 
     let title = if let Some(ref path) = app.current_file {
         format!(" {} ", path.file_name().unwrap_or_default().to_string_lossy())
+    } else if app.from_stdin {
+        format!(" stdin{} ", if app.is_reading_stdin() { " (reading…)" } else { "" })
     } else {
         " Content ".to_string()
     };
@@ -276,20 +580,41 @@ fn draw_content(f: &mut Frame, app: &App, area: Rect) {
     let max_width = app.settings.wrap_width.to_usize();
     let mut wrapped_lines: Vec<(Line, usize, bool)> = Vec::new(); // (line, source_idx, is_first)
 
+    // Highlight search matches, also after the prompt closed, the current one
+    // standing out from the rest
+    let highlight_style = Style::default().fg(colors.highlight_fg).bg(colors.highlight_bg);
+    let current_style = highlight_style.add_modifier(Modifier::REVERSED | Modifier::BOLD);
+    let mut line_matches: Vec<Vec<(Range<usize>, Style)>> = vec![Vec::new(); app.rendered_content.len()];
+    if app.doc_search_pattern.is_some() {
+        for (idx, m) in app.doc_search_matches.iter().enumerate() {
+            let style = if idx == app.doc_search_current { current_style } else { highlight_style };
+            if let Some(ranges) = line_matches.get_mut(m.line) {
+                ranges.push((m.range.clone(), style));
+            }
+        }
+    }
+
     for (source_idx, line) in app.rendered_content.iter().enumerate() {
         let wrapped = wrap_line(line, max_width, &colors);
+        let matches = &line_matches[source_idx];
+        let rows = if matches.is_empty() {
+            None
+        } else {
+            app::wrapped_rows(line, &line.to_plain_string(), max_width)
+        };
         for (i, wrapped_line) in wrapped.into_iter().enumerate() {
+            let wrapped_line = match rows {
+                _ if matches.is_empty() => wrapped_line,
+                Some(ref rows) => {
+                    let words = rows.get(i).map(Vec::as_slice).unwrap_or_default();
+                    highlight_ranges(wrapped_line, &ranges_on_row(words, matches))
+                }
+                None => highlight_ranges(wrapped_line, matches),
+            };
             wrapped_lines.push((wrapped_line, source_idx, i == 0));
         }
     }
 
-    // Check if we need to highlight search matches
-    let search_query = if app.mode == AppMode::DocumentSearch && !app.doc_search_query.is_empty() {
-        Some(app.doc_search_query.to_lowercase())
-    } else {
-        None
-    };
-
     // Disable line numbers in select mode for clean text selection
     let show_line_numbers = app.settings.show_line_numbers && app.mode != AppMode::Select;
 
@@ -303,7 +628,7 @@ fn draw_content(f: &mut Frame, app: &App, area: Rect) {
             .skip(app.content_scroll)
             .take(area.height.saturating_sub(2) as usize)
             .map(|(_wrapped_idx, (line, source_idx, is_first))| {
-                let is_match = app.doc_search_matches.contains(&source_idx);
+                let is_match = !line_matches[source_idx].is_empty();
 
                 let num_style = if is_match {
                     Style::default().fg(colors.highlight_fg)
@@ -325,11 +650,7 @@ fn draw_content(f: &mut Frame, app: &App, area: Rect) {
                 };
 
                 let mut spans = vec![num_span];
-                if let Some(ref query) = search_query {
-                    spans.extend(highlight_matches(line.spans, query, &colors));
-                } else {
-                    spans.extend(line.spans);
-                }
+                spans.extend(line.spans);
                 Line::from(spans)
             })
             .collect()
@@ -338,13 +659,7 @@ fn draw_content(f: &mut Frame, app: &App, area: Rect) {
             .into_iter()
             .skip(app.content_scroll)
             .take(area.height.saturating_sub(2) as usize)
-            .map(|(line, _source_idx, _is_first)| {
-                if let Some(ref query) = search_query {
-                    Line::from(highlight_matches(line.spans, query, &colors))
-                } else {
-                    line
-                }
-            })
+            .map(|(line, _source_idx, _is_first)| line)
             .collect()
     };
 
@@ -449,6 +764,7 @@ fn wrap_line(line: &RenderedLine, max_width: Option<usize>, colors: &ThemeColors
     result
 }
 
+#[allow(clippy::if_same_then_else)] // One branch per kind of line, even where two look the same
 fn get_line_style(line: &RenderedLine, colors: &ThemeColors) -> Style {
     if line.heading_level > 0 {
         get_heading_style(line.heading_level, colors)
@@ -514,14 +830,13 @@ fn render_line_with_theme(line: &RenderedLine, colors: &ThemeColors) -> Line<'st
 }
 
 
-fn draw_settings_overlay(f: &mut Frame, app: &App, area: Rect) {
-    // Calculate overlay size (centered, 50% width, 40% height)
-    let overlay_width = (area.width as f32 * 0.5) as u16;
-    let overlay_height = (area.height as f32 * 0.4) as u16;
-    let overlay_x = (area.width - overlay_width) / 2;
-    let overlay_y = (area.height - overlay_height) / 2;
+/// Settings overlay: centered, 50% width, 40% height
+fn settings_overlay_rect(area: Rect) -> Rect {
+    overlay_rect(area, 0.5, 0.4)
+}
 
-    let overlay_area = Rect::new(overlay_x, overlay_y, overlay_width, overlay_height);
+fn draw_settings_overlay(f: &mut Frame, app: &App, area: Rect) {
+    let overlay_area = settings_overlay_rect(area);
 
     // Clear the area
     f.render_widget(Clear, overlay_area);
@@ -567,6 +882,52 @@ fn draw_settings_overlay(f: &mut Frame, app: &App, area: Rect) {
         style,
     ))));
 
+    // Setting 3: Sidebar sort order (cycle)
+    let style = if app.settings_selected == 3 {
+        Style::default().bg(Color::Rgb(102, 217, 239)).fg(Color::Black)
+    } else {
+        Style::default().fg(Color::White)
+    };
+    items.push(ListItem::new(Line::from(Span::styled(
+        format!("    Sidebar sort: {}", app.settings.sort_mode.display_name()),
+        style,
+    ))));
+
+    // Setting 4: Modified date column (toggle)
+    let checkbox = if app.settings.show_modified_column { "[x]" } else { "[ ]" };
+    let style = if app.settings_selected == 4 {
+        Style::default().bg(Color::Rgb(102, 217, 239)).fg(Color::Black)
+    } else {
+        Style::default().fg(Color::White)
+    };
+    items.push(ListItem::new(Line::from(Span::styled(
+        format!("{} Show modified date in sidebar", checkbox),
+        style,
+    ))));
+
+    // Setting 5: Word count column (toggle)
+    let checkbox = if app.settings.show_word_count_column { "[x]" } else { "[ ]" };
+    let style = if app.settings_selected == 5 {
+        Style::default().bg(Color::Rgb(102, 217, 239)).fg(Color::Black)
+    } else {
+        Style::default().fg(Color::White)
+    };
+    items.push(ListItem::new(Line::from(Span::styled(
+        format!("{} Show word count in sidebar", checkbox),
+        style,
+    ))));
+
+    // Setting 6: Sidebar labels (cycle)
+    let style = if app.settings_selected == 6 {
+        Style::default().bg(Color::Rgb(102, 217, 239)).fg(Color::Black)
+    } else {
+        Style::default().fg(Color::White)
+    };
+    items.push(ListItem::new(Line::from(Span::styled(
+        format!("    Sidebar shows: {}", app.settings.sidebar_label.display_name()),
+        style,
+    ))));
+
     let settings_list = List::new(items).block(
         Block::default()
             .borders(Borders::ALL)
@@ -577,19 +938,9 @@ fn draw_settings_overlay(f: &mut Frame, app: &App, area: Rect) {
     f.render_widget(settings_list, overlay_area);
 }
 
-fn draw_search_overlay(f: &mut Frame, app: &App, area: Rect) {
-    // Calculate overlay size (centered, 60% width, 50% height)
-    let overlay_width = (area.width as f32 * 0.6) as u16;
-    let overlay_height = (area.height as f32 * 0.5) as u16;
-    let overlay_x = (area.width - overlay_width) / 2;
-    let overlay_y = (area.height - overlay_height) / 2;
-
-    let overlay_area = Rect::new(overlay_x, overlay_y, overlay_width, overlay_height);
-
-    // Clear the area
-    f.render_widget(Clear, overlay_area);
-
-    // Split into search input and results
+/// Search overlay (centered, 60% width, 50% height), split into input and results
+fn search_overlay_chunks(area: Rect) -> [Rect; 2] {
+    let overlay_area = overlay_rect(area, 0.6, 0.5);
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
@@ -597,28 +948,41 @@ fn draw_search_overlay(f: &mut Frame, app: &App, area: Rect) {
             Constraint::Min(1),
         ])
         .split(overlay_area);
+    [chunks[0], chunks[1]]
+}
+
+fn draw_search_overlay(f: &mut Frame, app: &App, area: Rect) {
+    let chunks = search_overlay_chunks(area);
+
+    // Clear the area
+    f.render_widget(Clear, overlay_rect(area, 0.6, 0.5));
 
     // Search input
-    let search_input = Paragraph::new(Line::from(vec![
+    let mut spans = vec![
         Span::raw(&app.search_query),
         Span::styled("_", Style::default().add_modifier(Modifier::SLOW_BLINK)),
-    ]))
+    ];
+    spans.extend(search_error_span(app));
+    let search_input = Paragraph::new(Line::from(spans))
     .block(
         Block::default()
             .borders(Borders::ALL)
             .border_style(Style::default().fg(Color::Rgb(102, 217, 239)))
-            .title(" Search "),
+            .title(format!(
+                " Search [{}] (^r: regex, ^f: fuzzy, M-c: case, M-w: word) ",
+                app.settings.search.label(true)
+            )),
     )
     .style(Style::default().fg(Color::White));
 
     f.render_widget(search_input, chunks[0]);
 
-    // Search results
-    let results: Vec<ListItem> = app
-        .search_results
-        .iter()
-        .enumerate()
-        .map(|(idx, result)| {
+    // Search results: each document, then its hits, as far as they fit
+    let list_height = inner(chunks[1]).height as usize;
+    let visible = app.search_scroll..app.search_rows.min(app.search_scroll + list_height);
+    let results: Vec<ListItem> = visible
+        .filter_map(|idx| Some((idx, app.search_row(idx)?)))
+        .map(|(idx, row)| {
             let is_selected = idx == app.search_selected;
 
             let name_style = if is_selected {
@@ -640,11 +1004,46 @@ fn draw_search_overlay(f: &mut Frame, app: &App, area: Rect) {
                 Style::default().fg(Color::White)
             };
 
-            ListItem::new(Line::from(vec![
-                Span::styled(&result.name, name_style),
-                Span::styled(": ", preview_style),
-                Span::styled(&result.match_preview, preview_style),
-            ]))
+            let file_style = if is_selected { preview_style } else { Style::default().fg(Color::DarkGray) };
+            let match_style = if is_selected {
+                preview_style.add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+            } else {
+                Style::default()
+                    .fg(Color::Rgb(166, 226, 46)) // Monokai green
+                    .add_modifier(Modifier::BOLD)
+            };
+            let spans = match row {
+                SearchRow::File(file) => {
+                    let result = &app.search_results[file];
+                    let mut spans = match result.title {
+                        Some(ref title) => vec![
+                            Span::styled(title.as_str(), name_style),
+                            Span::styled(format!(" ({})", result.name), file_style),
+                        ],
+                        None => vec![Span::styled(result.name.as_str(), name_style)],
+                    };
+                    match result.hits.len() {
+                        0 => {}
+                        1 => spans.push(Span::styled(" 1 match", file_style)),
+                        n => spans.push(Span::styled(format!(" {} matches", n), file_style)),
+                    }
+                    spans
+                }
+                SearchRow::Hit(file, hit) => {
+                    let hit = &app.search_results[file].hits[hit];
+                    let context = hit.context.as_str();
+                    let mut spans = vec![Span::styled(format!("{:>6}: ", hit.line + 1), file_style)];
+                    let mut pos = 0;
+                    for range in &hit.highlights {
+                        spans.push(Span::styled(&context[pos..range.start], preview_style));
+                        spans.push(Span::styled(&context[range.clone()], match_style));
+                        pos = range.end;
+                    }
+                    spans.push(Span::styled(&context[pos..], preview_style));
+                    spans
+                }
+            };
+            ListItem::new(Line::from(spans))
         })
         .collect();
 
@@ -652,57 +1051,109 @@ fn draw_search_overlay(f: &mut Frame, app: &App, area: Rect) {
         Block::default()
             .borders(Borders::ALL)
             .border_style(Style::default().fg(Color::DarkGray))
-            .title(format!(" Results ({}) ", app.search_results.len())),
+            .title(search_results_title(app)),
     );
 
     f.render_widget(results_list, chunks[1]);
 }
 
-fn highlight_matches(spans: Vec<Span<'static>>, query: &str, colors: &ThemeColors) -> Vec<Span<'static>> {
-    let mut result = Vec::new();
-    let highlight_style = Style::default()
-        .fg(colors.highlight_fg)
-        .bg(colors.highlight_bg);
-
-    for span in spans {
-        let text = span.content.to_string();
-        let text_lower = text.to_lowercase();
-        let style = span.style;
-
-        if text_lower.contains(query) {
-            let mut current_text = text.as_str();
-            let mut current_lower = text_lower.as_str();
-
-            while let Some(match_start) = current_lower.find(query) {
-                // Add text before match
-                if match_start > 0 {
-                    result.push(Span::styled(
-                        current_text[..match_start].to_string(),
-                        style,
-                    ));
-                }
+/// Match counts, and progress while the search is running
+fn search_results_title(app: &App) -> String {
+    let matches = app.search_rows - app.search_results.len();
+    let mut title = format!(
+        " Results ({} in {} documents",
+        group_digits(matches),
+        group_digits(app.search_results.len())
+    );
+    if let Some((done, total)) = app.search_indexing {
+        title.push_str(&format!(", indexing {}/{} files", group_digits(done), group_digits(total)));
+    } else if app.search_waiting {
+        let rest = app.search_total.saturating_sub(app.search_scanned);
+        title.push_str(&format!(", {} more files to search as you scroll", group_digits(rest)));
+    } else if app.is_searching() {
+        title.push_str(&format!(
+            ", scanned {}/{} files",
+            group_digits(app.search_scanned),
+            group_digits(app.search_total)
+        ));
+    }
+    title.push_str(") ");
+    title
+}
 
-                // Add highlighted match
-                let match_end = match_start + query.len();
-                result.push(Span::styled(
-                    current_text[match_start..match_end].to_string(),
-                    highlight_style,
-                ));
+/// `3400` as `3,400`
+fn group_digits(n: usize) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::new();
+    for (idx, c) in digits.chars().enumerate() {
+        if idx > 0 && (digits.len() - idx).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
 
-                // Move past this match
-                current_text = &current_text[match_end..];
-                current_lower = &current_lower[match_end..];
-            }
+/// Why the query doesn't compile, after the search input
+fn search_error_span(app: &App) -> Option<Span<'static>> {
+    app.search_error
+        .as_ref()
+        .map(|error| Span::styled(format!(" {}", error), Style::default().fg(Color::Rgb(249, 38, 114))))
+}
 
-            // Add remaining text
-            if !current_text.is_empty() {
-                result.push(Span::styled(current_text.to_string(), style));
+/// Style the byte `ranges` of a line, which may span several differently
+/// styled spans. The ranges are in order and don't overlap.
+fn highlight_ranges(line: Line<'static>, ranges: &[(Range<usize>, Style)]) -> Line<'static> {
+    if ranges.is_empty() {
+        return line;
+    }
+
+    let text: String = line.spans.iter().map(|span| span.content.as_ref()).collect();
+    let fits = |r: &Range<usize>| r.end <= text.len() && text.is_char_boundary(r.start) && text.is_char_boundary(r.end);
+    if !ranges.iter().all(|(r, _)| fits(r)) {
+        return line; // Matches of an earlier version of the line
+    }
+    let mut result = Vec::new();
+    let mut span_start = 0;
+    for span in line.spans {
+        let span_end = span_start + span.content.len();
+        let mut pos = span_start;
+        for (range, style) in ranges.iter().filter(|(r, _)| r.start < span_end && r.end > span_start) {
+            let (start, end) = (range.start.max(span_start), range.end.min(span_end));
+            if start > pos {
+                result.push(Span::styled(text[pos..start].to_string(), span.style));
             }
-        } else {
-            result.push(span);
+            result.push(Span::styled(text[start..end].to_string(), *style));
+            pos = end;
+        }
+        if pos < span_end {
+            result.push(Span::styled(text[pos..span_end].to_string(), span.style));
         }
+        span_start = span_end;
     }
 
+    Line::from(result)
+}
+
+/// Move byte ranges of a wrapped line's text onto one of its rows, whose
+/// `words` are drawn joined by single spaces
+fn ranges_on_row(words: &[Range<usize>], ranges: &[(Range<usize>, Style)]) -> Vec<(Range<usize>, Style)> {
+    let mut result = Vec::new();
+    let mut pos = 0; // Where the word starts on the row
+    for (idx, word) in words.iter().enumerate() {
+        for (range, style) in ranges {
+            let (start, mut end) = (range.start.max(word.start), range.end.min(word.end));
+            if start >= end {
+                continue;
+            }
+            // A match that goes on to the next word covers the space between them
+            if end == word.end && words.get(idx + 1).is_some_and(|next| range.end > next.start) {
+                end += 1;
+            }
+            result.push((pos + start - word.start..pos + end - word.start, *style));
+        }
+        pos += word.len() + 1;
+    }
     result
 }
 
@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::file_tree::IGNORE_FILENAME;
+
+/// Ignore files whose changes alter which entries the tree shows, the
+/// ones that take precedence last
+const IGNORE_FILES: &[&str] = &[".gitignore", ".ignore", IGNORE_FILENAME];
+
+/// Filesystem changes collected since the last poll
+#[derive(Debug, Default)]
+pub struct FsChanges {
+    /// Files whose contents changed
+    pub modified: Vec<PathBuf>,
+    /// Renames as (from, to) pairs
+    pub renames: Vec<(PathBuf, PathBuf)>,
+    /// Whether entries were created, removed or renamed
+    pub structure_changed: bool,
+    /// Whether an ignore file changed, which can show or hide entries anywhere below it
+    pub rules_changed: bool,
+    /// Whether the git index or HEAD moved (staging, commits, checkouts)
+    pub git_changed: bool,
+}
+
+impl FsChanges {
+    /// An entry created or removed by rmd itself
+    pub fn entry(path: &Path) -> Self {
+        Self {
+            modified: vec![path.to_path_buf()],
+            structure_changed: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.modified.is_empty()
+            && self.renames.is_empty()
+            && !self.structure_changed
+            && !self.rules_changed
+            && !self.git_changed
+    }
+
+    /// Whether `path` was modified, created or renamed into place
+    pub fn touches(&self, path: &Path) -> bool {
+        self.modified.iter().any(|p| p == path) || self.renames.iter().any(|(_, to)| to == path)
+    }
+}
+
+/// Recursive watcher on the root directory
+pub struct FsWatcher {
+    root: PathBuf,
+    _watcher: Arc<Mutex<Option<RecommendedWatcher>>>,
+    rx: Receiver<notify::Result<Event>>,
+    ignores: HashMap<PathBuf, Vec<Gitignore>>, // Each directory's ignore rules, read when first needed
+}
+
+impl FsWatcher {
+    /// Start watching in the background, since registering a recursive watch
+    /// walks the whole tree. Without a working watcher nothing is reported.
+    pub fn new(root: &Path) -> Self {
+        let (tx, rx) = channel();
+        let slot = Arc::new(Mutex::new(None));
+
+        let watcher_slot = Arc::clone(&slot);
+        let watch_root = root.to_path_buf();
+        thread::spawn(move || {
+            let watcher = notify::recommended_watcher(move |res| {
+                let _ = tx.send(res);
+            })
+            .and_then(|mut watcher| {
+                watcher.watch(&watch_root, RecursiveMode::Recursive)?;
+                Ok(watcher)
+            });
+            if let (Ok(watcher), Ok(mut slot)) = (watcher, watcher_slot.lock()) {
+                *slot = Some(watcher);
+            }
+        });
+
+        Self {
+            root: root.to_path_buf(),
+            _watcher: slot,
+            rx,
+            ignores: HashMap::new(),
+        }
+    }
+
+    /// Drain all pending events without blocking. Changes to ignored entries
+    /// (like `target/` or `node_modules/`) are dropped unless `show_ignored`.
+    pub fn poll_changes(&mut self, show_ignored: bool) -> FsChanges {
+        let mut changes = FsChanges::default();
+
+        while let Ok(res) = self.rx.try_recv() {
+            let Ok(event) = res else { continue };
+            // Reading files (the ignore files, git's index) changes nothing
+            if matches!(event.kind, EventKind::Access(_)) {
+                continue;
+            }
+            for path in event.paths.iter().filter(|p| is_ignore_file(p)) {
+                let dir = path.parent().unwrap_or(path);
+                if !self.is_hidden(dir) && !self.is_ignored(dir) {
+                    self.ignores.remove(dir);
+                    changes.rules_changed = true;
+                }
+            }
+            if event.paths.iter().any(|p| is_git_state_file(p)) {
+                changes.git_changed = true;
+            }
+            let paths: Vec<PathBuf> = event
+                .paths
+                .into_iter()
+                .filter(|p| !self.is_hidden(p) && (show_ignored || !self.is_ignored(p)))
+                .collect();
+            if paths.is_empty() {
+                continue;
+            }
+
+            match event.kind {
+                EventKind::Create(_) | EventKind::Remove(_) => {
+                    changes.structure_changed = true;
+                    changes.modified.extend(paths);
+                }
+                EventKind::Modify(ModifyKind::Name(mode)) => {
+                    changes.structure_changed = true;
+                    match mode {
+                        // Linux also sends the two halves on their own first
+                        RenameMode::Both if paths.len() == 2 => {
+                            changes.renames.push((paths[0].clone(), paths[1].clone()));
+                        }
+                        // Without its other half, a move into or out of the
+                        // root: the entry appears or disappears
+                        _ => changes.modified.extend(paths),
+                    }
+                }
+                EventKind::Modify(_) | EventKind::Any => changes.modified.extend(paths),
+                _ => {}
+            }
+        }
+
+        changes.modified.sort();
+        changes.modified.dedup();
+        let renamed: Vec<&PathBuf> = changes.renames.iter().flat_map(|(from, to)| [from, to]).collect();
+        changes.modified.retain(|path| !renamed.contains(&path));
+        changes
+    }
+
+    /// Whether the ignore files of the directories above `path` exclude it
+    /// or a directory it is in. Deeper rules win, and within a directory
+    /// `.rmdignore` over `.ignore` over `.gitignore`, as for the tree.
+    fn is_ignored(&mut self, path: &Path) -> bool {
+        let is_dir = path.is_dir();
+        let dirs: Vec<PathBuf> = path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(&self.root))
+            .map(Path::to_path_buf)
+            .collect();
+        for dir in dirs {
+            let rules = self.ignores.entry(dir.clone()).or_insert_with(|| read_ignore_files(&dir));
+            for rules in rules.iter() {
+                let matched = rules.matched_path_or_any_parents(path, is_dir);
+                if matched.is_ignore() {
+                    return true;
+                } else if matched.is_whitelist() {
+                    return false;
+                }
+            }
+        }
+        false
+    }
+
+    /// Skip changes inside hidden entries (.git, editor swap files, ...)
+    fn is_hidden(&self, path: &Path) -> bool {
+        path.strip_prefix(&self.root)
+            .map(|rel| {
+                rel.components()
+                    .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+            })
+            .unwrap_or(true)
+    }
+}
+
+/// The ignore files in `dir`, the one that takes precedence first
+fn read_ignore_files(dir: &Path) -> Vec<Gitignore> {
+    IGNORE_FILES
+        .iter()
+        .rev()
+        .map(|name| dir.join(name))
+        .filter(|file| file.is_file())
+        .filter_map(|file| {
+            let mut builder = GitignoreBuilder::new(dir);
+            builder.add(file);
+            builder.build().ok()
+        })
+        .collect()
+}
+
+fn is_ignore_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| IGNORE_FILES.contains(&name))
+        .unwrap_or(false)
+}
+
+/// Files inside `.git` whose changes alter `git status`
+fn is_git_state_file(path: &Path) -> bool {
+    let name = path.file_name().and_then(|name| name.to_str());
+    matches!(name, Some("index" | "HEAD"))
+        && path.parent().and_then(|p| p.file_name()).map(|n| n == ".git").unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::{Duration, Instant};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rmd-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn ready_watcher(root: &Path) -> FsWatcher {
+        let watcher = FsWatcher::new(root);
+        let start = Instant::now();
+        while watcher._watcher.lock().unwrap().is_none() && start.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        watcher
+    }
+
+    /// Changes until one of them satisfies `done`, or a few seconds pass
+    fn changes_until(watcher: &mut FsWatcher, done: impl Fn(&FsChanges) -> bool) -> FsChanges {
+        let mut all = FsChanges::default();
+        let start = Instant::now();
+        while !done(&all) && start.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(50));
+            let changes = watcher.poll_changes(false);
+            all.modified.extend(changes.modified);
+            all.renames.extend(changes.renames);
+        }
+        all
+    }
+
+    #[test]
+    fn moves_across_the_root_are_reported_as_changes() {
+        let dir = temp_dir("watch-moves");
+        let (root, outside) = (dir.join("root"), dir.join("outside"));
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(outside.join("in.md"), "# In").unwrap();
+        fs::write(root.join("out.md"), "# Out").unwrap();
+        let mut watcher = ready_watcher(&root);
+
+        fs::rename(outside.join("in.md"), root.join("in.md")).unwrap();
+        let changes = changes_until(&mut watcher, |c| !c.modified.is_empty());
+        assert_eq!(changes.modified, [root.join("in.md")]);
+        assert!(changes.renames.is_empty());
+
+        fs::rename(root.join("out.md"), outside.join("out.md")).unwrap();
+        let changes = changes_until(&mut watcher, |c| !c.modified.is_empty());
+        assert_eq!(changes.modified, [root.join("out.md")]);
+        assert!(changes.renames.is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rename_within_the_root_is_reported_once() {
+        let root = temp_dir("watch-rename");
+        fs::write(root.join("a.md"), "# A").unwrap();
+        let mut watcher = ready_watcher(&root);
+
+        fs::rename(root.join("a.md"), root.join("b.md")).unwrap();
+        let changes = changes_until(&mut watcher, |c| !c.renames.is_empty());
+        assert_eq!(changes.renames, [(root.join("a.md"), root.join("b.md"))]);
+        assert!(changes.modified.is_empty());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}